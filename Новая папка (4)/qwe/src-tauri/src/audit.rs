@@ -0,0 +1,214 @@
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+use crate::card::EntryKind;
+use crate::crypto;
+use crate::error::StoreError;
+use crate::{pwned, strength, PasswordEntry};
+
+pub const DEFAULT_WEAK_SCORE: u8 = 3;
+pub const DEFAULT_MAX_AGE_DAYS: u64 = 365;
+
+#[derive(Debug, Serialize)]
+pub struct WeakEntry {
+    pub id: String,
+    pub score: u8,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PwnedEntry {
+    pub id: String,
+    pub count: u64,
+}
+
+// Пароли в отчет не попадают, только id записей
+#[derive(Debug, Default, Serialize)]
+pub struct AuditReport {
+    pub reused: Vec<Vec<String>>,
+    pub weak: Vec<WeakEntry>,
+    pub stale: Vec<String>,
+    // None, если проверка по HIBP не запрашивалась
+    pub pwned: Option<Vec<PwnedEntry>>,
+    pub pwned_offline: bool,
+}
+
+pub struct AuditOptions {
+    pub weak_score: u8,
+    pub max_age_days: u64,
+    pub now: u64,
+}
+
+// Записи с одинаковым паролем. Ключом HashMap служит HMAC-SHA256 пароля
+// со случайным ключом на каждый вызов, а не сам пароль: хеширование и
+// сравнение ключей не работают с открытым текстом. Совпадение дайджестов
+// подтверждается сравнением в постоянное время
+fn same_password_groups<'a>(entries: impl Iterator<Item = &'a PasswordEntry>) -> Vec<Vec<&'a PasswordEntry>> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    OsRng.fill_bytes(key.as_mut());
+
+    let mut buckets: HashMap<[u8; 32], Vec<Vec<&PasswordEntry>>> = HashMap::new();
+    for entry in entries.filter(|e| !e.password.is_empty()) {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.as_ref()).expect("HMAC accepts any key length");
+        mac.update(entry.password.as_bytes());
+        let bucket = buckets.entry(mac.finalize().into_bytes().into()).or_default();
+        match bucket
+            .iter_mut()
+            .find(|group| crypto::secrets_equal(&group[0].password, &entry.password))
+        {
+            Some(group) => group.push(entry),
+            None => bucket.push(vec![entry]),
+        }
+    }
+    buckets.into_values().flatten().collect()
+}
+
+// Группируем id по совпадающему паролю
+pub fn reused_groups<'a>(entries: impl Iterator<Item = &'a PasswordEntry>) -> Vec<Vec<String>> {
+    let mut groups: Vec<Vec<String>> = same_password_groups(entries)
+        .into_iter()
+        .filter(|group| group.len() > 1)
+        .map(|group| {
+            let mut ids: Vec<String> = group.iter().map(|entry| entry.id.clone()).collect();
+            ids.sort();
+            ids
+        })
+        .collect();
+    groups.sort();
+    groups
+}
+
+pub fn audit(entries: &[&PasswordEntry], options: &AuditOptions) -> AuditReport {
+    let cutoff = options.now.saturating_sub(options.max_age_days * 24 * 60 * 60);
+
+    let mut report = AuditReport {
+        reused: reused_groups(entries.iter().copied()),
+        ..AuditReport::default()
+    };
+    for entry in entries {
+        let score = strength::estimate(&entry.password).score;
+        if score < options.weak_score {
+            report.weak.push(WeakEntry {
+                id: entry.id.clone(),
+                score,
+            });
+        }
+        if entry.updated_at < cutoff {
+            report.stale.push(entry.id.clone());
+        }
+    }
+    report.weak.sort_by(|a, b| a.id.cmp(&b.id));
+    report.stale.sort();
+    report
+}
+
+// Сводка для главного экрана. В отличие от отчета аудита здесь только
+// счетчики, без id записей. Записи в корзине считаются отдельно
+#[derive(Debug, Default, Serialize)]
+pub struct VaultStats {
+    pub total: usize,
+    pub trashed: usize,
+    pub by_kind: HashMap<EntryKind, usize>,
+    pub by_tag: HashMap<String, usize>,
+    pub weak: usize,
+    // Записи, пароль которых встречается еще хотя бы в одной
+    pub reused: usize,
+    pub with_totp: usize,
+    pub favorites: usize,
+    pub oldest_entry_age_days: Option<u64>,
+}
+
+pub fn stats<'a>(entries: impl Iterator<Item = &'a PasswordEntry>, weak_score: u8, now: u64) -> VaultStats {
+    let mut stats = VaultStats::default();
+    let mut with_password = Vec::new();
+    let mut oldest: Option<u64> = None;
+
+    for entry in entries {
+        if entry.is_trashed() {
+            stats.trashed += 1;
+            continue;
+        }
+        stats.total += 1;
+        *stats.by_kind.entry(entry.kind).or_default() += 1;
+        for tag in &entry.tags {
+            *stats.by_tag.entry(tag.clone()).or_default() += 1;
+        }
+        if entry.totp_secret.is_some() {
+            stats.with_totp += 1;
+        }
+        if entry.is_favorite {
+            stats.favorites += 1;
+        }
+        oldest = Some(oldest.map_or(entry.created_at, |oldest| oldest.min(entry.created_at)));
+
+        // У карт и заметок пароля может не быть
+        if !entry.password.is_empty() {
+            with_password.push(entry);
+            if strength::estimate(&entry.password).score < weak_score {
+                stats.weak += 1;
+            }
+        }
+    }
+
+    stats.reused = same_password_groups(with_password.into_iter())
+        .iter()
+        .filter(|group| group.len() > 1)
+        .map(Vec::len)
+        .sum();
+    stats.oldest_entry_age_days = oldest.map(|created_at| now.saturating_sub(created_at) / (24 * 60 * 60));
+    stats
+}
+
+// Проверка идет по сети, поэтому выполняется вне блокировки хранилища
+pub fn check_pwned(
+    report: &mut AuditReport,
+    passwords: &[(String, Zeroizing<String>)],
+    timeout: Duration,
+) -> Result<(), StoreError> {
+    let mut found = Vec::new();
+    for (id, password) in passwords {
+        let result = pwned::check(password, timeout)?;
+        if result.offline {
+            report.pwned_offline = true;
+            break;
+        }
+        if result.pwned {
+            found.push(PwnedEntry {
+                id: id.clone(),
+                count: result.count,
+            });
+        }
+    }
+    report.pwned = Some(found);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_password(password: &str) -> PasswordEntry {
+        PasswordEntry::new("site".to_string(), "user".to_string(), password.to_string(), None, None)
+    }
+
+    #[test]
+    fn entries_sharing_a_password_are_grouped() {
+        let entries = [
+            with_password("hunter22"),
+            with_password("hunter22"),
+            with_password("other-pass"),
+            with_password(""),
+        ];
+        let mut expected = vec![entries[0].id.clone(), entries[1].id.clone()];
+        expected.sort();
+        assert_eq!(reused_groups(entries.iter()), vec![expected]);
+
+        let stats = stats(entries.iter(), DEFAULT_WEAK_SCORE, 0);
+        assert_eq!(stats.reused, 2);
+    }
+}
@@ -0,0 +1,60 @@
+use data_encoding::{BASE64, HEXLOWER};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{crypto, schema};
+use crate::error::StoreError;
+
+const BACKUP_FORMAT: &str = "password-manager-backup";
+const BACKUP_VERSION: u32 = 1;
+
+// Резервная копия — зашифрованное содержимое хранилища в обертке с версией
+// и контрольной суммой, чтобы поврежденный файл не затер рабочие данные
+#[derive(Serialize, Deserialize)]
+struct BackupFile {
+    format: String,
+    version: u32,
+    created_at: u64,
+    checksum: String,
+    payload: String,
+}
+
+fn checksum(payload: &[u8]) -> String {
+    HEXLOWER.encode(&Sha256::digest(payload))
+}
+
+pub fn encode(vault: &[u8], created_at: u64) -> Result<Vec<u8>, StoreError> {
+    let backup = BackupFile {
+        format: BACKUP_FORMAT.to_string(),
+        version: BACKUP_VERSION,
+        created_at,
+        checksum: checksum(vault),
+        payload: BASE64.encode(vault),
+    };
+
+    serde_json::to_vec_pretty(&backup)
+        .map_err(|e| StoreError::Serialize(format!("Failed to serialize backup: {}", e)))
+}
+
+// Возвращает содержимое хранилища только если файл прошел все проверки
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, StoreError> {
+    let backup: BackupFile = serde_json::from_slice(data)
+        .map_err(|e| schema::redacted_json_error("Backup file is not readable", &e))?;
+
+    if backup.format != BACKUP_FORMAT {
+        return Err(StoreError::Validation("File is not a password manager backup".to_string()));
+    }
+    if backup.version != BACKUP_VERSION {
+        return Err(StoreError::Validation(format!("Unsupported backup version: {}", backup.version)));
+    }
+
+    let vault = BASE64
+        .decode(backup.payload.as_bytes())
+        .map_err(|_| StoreError::Validation("Backup payload is corrupted".to_string()))?;
+    if checksum(&vault) != backup.checksum {
+        return Err(StoreError::Validation("Backup checksum mismatch".to_string()));
+    }
+
+    crypto::read_header(&vault)?;
+    Ok(vault)
+}
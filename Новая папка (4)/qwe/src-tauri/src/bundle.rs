@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+use crate::error::StoreError;
+use crate::{compress, crypto, schema, PasswordEntry};
+
+// Переносимый зашифрованный файл для синхронизации: ключ выводится из
+// парольной фразы экспорта, а не из мастер-пароля, поэтому файл можно
+// открыть на другом устройстве. Формат: MAGIC | версия | блок crypto::encrypt
+const BUNDLE_MAGIC: &[u8; 4] = b"PMEX";
+const BUNDLE_VERSION: u8 = 1;
+const HEADER_LEN: usize = BUNDLE_MAGIC.len() + 1;
+
+pub fn is_bundle(data: &[u8]) -> bool {
+    data.starts_with(BUNDLE_MAGIC)
+}
+
+pub fn seal(entries: &HashMap<String, PasswordEntry>, passphrase: &str) -> Result<Vec<u8>, StoreError> {
+    let key = crypto::derive_key(passphrase, crypto::KdfParams::default(), crypto::generate_salt())?;
+    let json = schema::serialize(entries)?;
+    let encrypted = crypto::encrypt(&key, &compress::compress(&json)?)?;
+
+    let mut out = Vec::with_capacity(HEADER_LEN + encrypted.len());
+    out.extend_from_slice(BUNDLE_MAGIC);
+    out.push(BUNDLE_VERSION);
+    out.extend_from_slice(&encrypted);
+    Ok(out)
+}
+
+// Измененный файл не пройдет проверку AEAD-тега и вернет IncorrectPassword
+pub fn open(data: &[u8], passphrase: &str) -> Result<HashMap<String, PasswordEntry>, StoreError> {
+    if data.len() < HEADER_LEN || !data.starts_with(BUNDLE_MAGIC) {
+        return Err(StoreError::Validation("File is not an encrypted export".to_string()));
+    }
+    let version = data[BUNDLE_MAGIC.len()];
+    if version != BUNDLE_VERSION {
+        return Err(StoreError::Validation(format!("Unsupported export version: {}", version)));
+    }
+
+    let encrypted = &data[HEADER_LEN..];
+    let key = crypto::derive_key_for(passphrase, encrypted)?;
+    let plaintext = compress::decompress(&crypto::decrypt(&key, encrypted)?)?;
+    Ok(schema::load(&plaintext)?.entries)
+}
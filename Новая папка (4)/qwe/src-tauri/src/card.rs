@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroize;
+
+use crate::crypto;
+use crate::error::StoreError;
+
+// Тип записи. Поля логина (username, password, url) есть у всех, но
+// обязателен пароль только у Login; у Card данные карты лежат в card
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EntryKind {
+    #[default]
+    Login,
+    Card,
+    Note,
+    Identity,
+}
+
+// Номер хранится без пробелов и дефисов, срок — в виде MM/YY
+#[derive(Clone, Default, Serialize, Deserialize, Zeroize)]
+pub struct CardDetails {
+    #[serde(default)]
+    pub holder: String,
+    pub number: String,
+    #[serde(default)]
+    pub expiry: Option<String>,
+    #[serde(default)]
+    pub cvv: Option<String>,
+}
+
+impl fmt::Debug for CardDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redact = |value: &Option<String>| value.as_ref().map(|_| crate::REDACTED);
+        f.debug_struct("CardDetails")
+            .field("holder", &self.holder)
+            .field("number", &crate::REDACTED)
+            .field("expiry", &self.expiry)
+            .field("cvv", &redact(&self.cvv))
+            .finish()
+    }
+}
+
+impl PartialEq for CardDetails {
+    fn eq(&self, other: &Self) -> bool {
+        self.holder == other.holder
+            && self.expiry == other.expiry
+            && crypto::secrets_equal(&self.number, &other.number)
+            && match (&self.cvv, &other.cvv) {
+                (Some(a), Some(b)) => crypto::secrets_equal(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+// Алгоритм Луна: удваивается каждая вторая цифра справа
+pub fn luhn_valid(number: &str) -> bool {
+    let mut sum = 0;
+    for (index, c) in number.chars().rev().enumerate() {
+        let Some(mut digit) = c.to_digit(10) else {
+            return false;
+        };
+        if index % 2 == 1 {
+            digit *= 2;
+            if digit > 9 {
+                digit -= 9;
+            }
+        }
+        sum += digit;
+    }
+    sum % 10 == 0
+}
+
+impl CardDetails {
+    // Приводит поля к хранимому виду и проверяет их
+    pub fn normalize(&mut self) -> Result<(), StoreError> {
+        self.holder = self.holder.trim().to_string();
+
+        let number: String = self.number.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+        self.number.zeroize();
+        self.number = number;
+        if !(12..=19).contains(&self.number.len()) || !luhn_valid(&self.number) {
+            return Err(StoreError::Validation("card.number: not a valid card number".to_string()));
+        }
+
+        self.expiry = self.expiry.take().map(|e| e.trim().to_string()).filter(|e| !e.is_empty());
+        if let Some(expiry) = &self.expiry {
+            let valid = match expiry.split_once('/') {
+                Some((month, year)) => {
+                    month.len() == 2
+                        && year.len() == 2
+                        && year.chars().all(|c| c.is_ascii_digit())
+                        && month.parse::<u8>().is_ok_and(|m| (1..=12).contains(&m))
+                }
+                None => false,
+            };
+            if !valid {
+                return Err(StoreError::Validation("card.expiry: expected MM/YY".to_string()));
+            }
+        }
+
+        self.cvv = self.cvv.take().map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+        if let Some(cvv) = &self.cvv {
+            if !(3..=4).contains(&cvv.len()) || !cvv.chars().all(|c| c.is_ascii_digit()) {
+                return Err(StoreError::Validation("card.cvv: must be 3 or 4 digits".to_string()));
+            }
+        }
+        Ok(())
+    }
+}
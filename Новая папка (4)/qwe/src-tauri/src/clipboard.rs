@@ -0,0 +1,101 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use zeroize::Zeroizing;
+
+use crate::crypto;
+use crate::error::StoreError;
+use crate::sync::MutexExt;
+
+// Последний секрет, который приложение положило в буфер обмена. Буфер
+// очищается, только если в нем все еще этот секрет: то, что пользователь
+// скопировал позже сам, не трогаем. Общий для всех хранилищ
+pub struct ClipboardGuard {
+    copied: Mutex<Option<Zeroizing<String>>>,
+    // Настройка clipboard_clear_secs; 0 — фоновая очистка отключена
+    clear_after_secs: Mutex<u64>,
+}
+
+// В буфере по-прежнему наш секрет, а не что-то скопированное после него
+fn still_ours(current: Option<&str>, ours: &str) -> bool {
+    current.is_some_and(|text| crypto::secrets_equal(text, ours))
+}
+
+impl ClipboardGuard {
+    pub fn new(clear_after_secs: u64) -> Self {
+        Self {
+            copied: Mutex::new(None),
+            clear_after_secs: Mutex::new(clear_after_secs),
+        }
+    }
+
+    pub fn clear_after_secs(&self) -> u64 {
+        *self.clear_after_secs.lock_recover()
+    }
+
+    pub fn set_clear_after_secs(&self, secs: u64) {
+        *self.clear_after_secs.lock_recover() = secs;
+    }
+
+    // Таймер очистки — отдельный поток с AppHandle, он не зависит от команды,
+    // которая его запустила, и переживает ее завершение
+    // Без clear_after пароль остается в буфере до выхода из приложения
+    // или до того, как пользователь скопирует что-то другое
+    pub fn copy(&self, app: &AppHandle, secret: Zeroizing<String>, clear_after: Option<Duration>) -> Result<(), StoreError> {
+        let mut copied = self.copied.lock_recover();
+        app.clipboard()
+            .write_text(secret.as_str())
+            .map_err(|e| StoreError::Io(format!("Failed to write to clipboard: {}", e)))?;
+        *copied = Some(secret.clone());
+        drop(copied);
+
+        let Some(clear_after) = clear_after else {
+            return Ok(());
+        };
+        let handle = app.clone();
+        thread::spawn(move || {
+            thread::sleep(clear_after);
+            handle.state::<ClipboardGuard>().clear_if_ours(&handle, Some(secret.as_str()));
+        });
+        Ok(())
+    }
+
+    // expected — секрет конкретного копирования: таймер старого копирования
+    // не должен стирать пароль, скопированный после него. None — любой наш
+    // секрет, так буфер очищается при выходе
+    pub fn clear_if_ours(&self, app: &AppHandle, expected: Option<&str>) {
+        let mut copied = self.copied.lock_recover();
+        let Some(ours) = copied.as_ref() else {
+            return;
+        };
+        if expected.is_some_and(|expected| !crypto::secrets_equal(expected, ours)) {
+            return;
+        }
+
+        let current = app.clipboard().read_text().ok().map(Zeroizing::new);
+        if still_ours(current.as_deref().map(String::as_str), ours) {
+            if let Err(e) = app.clipboard().clear() {
+                log::warn!("Failed to clear clipboard: {}", e);
+                return;
+            }
+        }
+        copied.take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipboard_is_cleared_only_while_it_holds_our_secret() {
+        assert!(still_ours(Some("s3cret"), "s3cret"));
+        // Пользователь скопировал что-то свое, буфер не трогаем
+        assert!(!still_ours(Some("shopping list"), "s3cret"));
+        assert!(!still_ours(Some("s3cret "), "s3cret"));
+        // Буфер пуст или в нем не текст
+        assert!(!still_ours(None, "s3cret"));
+    }
+}
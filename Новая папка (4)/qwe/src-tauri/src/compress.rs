@@ -0,0 +1,32 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+use zeroize::Zeroizing;
+
+use crate::error::StoreError;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+// Сжатие выполняется до шифрования: шифротекст уже не сжимается
+pub fn compress(data: &[u8]) -> Result<Zeroizing<Vec<u8>>, StoreError> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .map(Zeroizing::new)
+        .map_err(|e| StoreError::Serialize(format!("Failed to compress passwords: {}", e)))
+}
+
+// Файлы, записанные до появления сжатия, содержат JSON как есть
+pub fn decompress(data: &[u8]) -> Result<Zeroizing<Vec<u8>>, StoreError> {
+    if !data.starts_with(GZIP_MAGIC) {
+        return Ok(Zeroizing::new(data.to_vec()));
+    }
+
+    let mut out = Zeroizing::new(Vec::new());
+    GzDecoder::new(data)
+        .read_to_end(&mut out)
+        .map_err(|e| StoreError::Serialize(format!("Failed to decompress passwords: {}", e)))?;
+    Ok(out)
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::error::StoreError;
+use crate::generator::GenerationSettings;
+use crate::templates::EntryTemplate;
+
+pub const DEFAULT_CLIPBOARD_CLEAR_SECS: u64 = 30;
+
+// Переменная окружения имеет приоритет над сохраненной настройкой
+pub const VAULT_DIR_ENV: &str = "PASSWORD_MANAGER_VAULT_DIR";
+pub const VAULT_FILE_NAME: &str = "passwords.json";
+const CONFIG_FILE_NAME: &str = "config.json";
+// Хранилище, которое было единственным до появления именованных;
+// его каталог по-прежнему задается vault_dir и переменной окружения
+pub const DEFAULT_VAULT: &str = "default";
+const NAMED_VAULTS_DIR: &str = "vaults";
+const MAX_VAULT_NAME_LEN: usize = 64;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppConfig {
+    #[serde(default)]
+    pub vault_dir: Option<PathBuf>,
+    // Каталоги именованных хранилищ, кроме основного
+    #[serde(default)]
+    pub vaults: BTreeMap<String, PathBuf>,
+    #[serde(default)]
+    pub generation: GenerationSettings,
+    #[serde(default)]
+    pub templates: BTreeMap<String, EntryTemplate>,
+    // Секреты записей хранятся в памяти зашифрованными и расшифровываются
+    // на время команды: меньше открытых данных в RAM ценой лишнего шифрования
+    #[serde(default)]
+    pub lazy_decryption: bool,
+    // Через сколько секунд очищать буфер после копирования пароля; 0 — никогда
+    #[serde(default = "default_clipboard_clear_secs")]
+    pub clipboard_clear_secs: u64,
+    // Сжимать ли JSON перед шифрованием. Без сжатия файл больше, зато размер
+    // шифротекста не зависит от того, насколько похожи записи друг на друга
+    #[serde(default = "default_compress_vault")]
+    pub compress_vault: bool,
+}
+
+fn default_clipboard_clear_secs() -> u64 {
+    DEFAULT_CLIPBOARD_CLEAR_SECS
+}
+
+fn default_compress_vault() -> bool {
+    true
+}
+
+// Без файла настроек действуют те же значения, что и при пустом файле
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            vault_dir: None,
+            vaults: BTreeMap::new(),
+            generation: GenerationSettings::default(),
+            templates: BTreeMap::new(),
+            lazy_decryption: false,
+            clipboard_clear_secs: DEFAULT_CLIPBOARD_CLEAR_SECS,
+            compress_vault: true,
+        }
+    }
+}
+
+pub fn app_data_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("./data"))
+        .join("password-manager")
+}
+
+// Отсутствующий или поврежденный файл настроек не мешает запуску
+pub fn load(app_dir: &Path) -> AppConfig {
+    match fs::read(app_dir.join(CONFIG_FILE_NAME)) {
+        Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+            log::warn!("Failed to parse config file: {}", e);
+            AppConfig::default()
+        }),
+        Err(e) if e.kind() == ErrorKind::NotFound => AppConfig::default(),
+        Err(e) => {
+            log::warn!("Error reading config file: {}", e);
+            AppConfig::default()
+        }
+    }
+}
+
+pub fn save(app_dir: &Path, config: &AppConfig) -> Result<(), StoreError> {
+    let json = serde_json::to_vec_pretty(config)
+        .map_err(|e| StoreError::Serialize(format!("Failed to serialize config: {}", e)))?;
+    crate::write_atomically(&app_dir.join(CONFIG_FILE_NAME), &json)
+}
+
+pub fn vault_dir(app_dir: &Path, config: &AppConfig) -> PathBuf {
+    std::env::var_os(VAULT_DIR_ENV)
+        .map(PathBuf::from)
+        .or_else(|| config.vault_dir.clone())
+        .unwrap_or_else(|| app_dir.to_path_buf())
+}
+
+// Каталог нового именованного хранилища внутри каталога приложения
+pub fn named_vault_dir(app_dir: &Path, name: &str) -> PathBuf {
+    app_dir.join(NAMED_VAULTS_DIR).join(name)
+}
+
+pub fn set_vault_dir(config: &mut AppConfig, name: &str, dir: PathBuf) {
+    if name == DEFAULT_VAULT {
+        config.vault_dir = Some(dir);
+    } else {
+        config.vaults.insert(name.to_string(), dir);
+    }
+}
+
+// Имя становится именем каталога, поэтому допускаются только безопасные символы
+pub fn validate_vault_name(name: &str) -> Result<(), StoreError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_VAULT_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(StoreError::Validation(format!(
+            "Vault name must be 1 to {} letters, digits, '-' or '_'",
+            MAX_VAULT_NAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+// Пробная запись показывает, что в каталог действительно можно писать
+pub fn ensure_writable(dir: &Path) -> Result<(), StoreError> {
+    fs::create_dir_all(dir)
+        .map_err(|e| StoreError::Io(format!("Failed to create vault directory: {}", e)))?;
+
+    let probe = dir.join(".write-test");
+    fs::write(&probe, b"")
+        .map_err(|e| StoreError::Validation(format!("Vault directory is not writable: {}", e)))?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
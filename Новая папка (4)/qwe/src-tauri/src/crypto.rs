@@ -0,0 +1,467 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::error::StoreError;
+
+// Формат файла: MAGIC | версия | параметры KDF | соль | nonce | шифротекст.
+// Версия 1 не хранила параметры KDF и читается с LEGACY.
+// Версия 3 (с ключом восстановления) после соли хранит слоты ключа:
+// слот пароля | параметры KDF и соль ключа восстановления | слот восстановления.
+// Данные в ней шифруются случайным ключом, а слоты — это он же,
+// зашифрованный ключом из мастер-пароля и ключом из кода восстановления
+pub const VAULT_MAGIC: &[u8; 4] = b"PMGR";
+pub const VAULT_VERSION: u8 = 2;
+const LEGACY_VAULT_VERSION: u8 = 1;
+const RECOVERY_VAULT_VERSION: u8 = 3;
+
+pub const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+const PARAMS_LEN: usize = 12;
+const SLOT_LEN: usize = NONCE_LEN + KEY_LEN + 16;
+const SLOTS_LEN: usize = SLOT_LEN + PARAMS_LEN + SALT_LEN + SLOT_LEN;
+// 160 бит; код показывается группами по 4 символа base32
+const RECOVERY_KEY_BYTES: usize = 20;
+const RECOVERY_GROUP_LEN: usize = 4;
+
+// Верхние границы защищают от подмененного заголовка: память под Argon2
+// выделяется до проверки тега, поэтому больше 1 GiB заголовок не запросит
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 64;
+const MAX_PARALLELISM: u32 = 16;
+
+// Стоимость Argon2id. Хранится в заголовке, поэтому ее можно повышать
+// для новых файлов, не ломая чтение старых
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    // Значения argon2 по умолчанию (19 MiB, 2 итерации, 1 поток), которыми
+    // шифровались файлы версии 1
+    pub const LEGACY: KdfParams = KdfParams {
+        memory_kib: 19 * 1024,
+        iterations: 2,
+        parallelism: 1,
+    };
+
+    fn to_argon2(self) -> Result<Params, StoreError> {
+        if self.memory_kib > MAX_MEMORY_KIB
+            || self.iterations > MAX_ITERATIONS
+            || self.parallelism > MAX_PARALLELISM
+        {
+            return Err(StoreError::Validation("KDF parameters exceed supported limits".to_string()));
+        }
+        Params::new(self.memory_kib, self.iterations, self.parallelism, Some(KEY_LEN))
+            .map_err(|e| StoreError::Validation(format!("Invalid KDF parameters: {}", e)))
+    }
+
+    pub fn validate(&self) -> Result<(), StoreError> {
+        self.to_argon2().map(|_| ())
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.memory_kib.to_le_bytes());
+        out.extend_from_slice(&self.iterations.to_le_bytes());
+        out.extend_from_slice(&self.parallelism.to_le_bytes());
+    }
+
+    fn read(data: &[u8]) -> Self {
+        let field = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        Self {
+            memory_kib: field(0),
+            iterations: field(4),
+            parallelism: field(8),
+        }
+    }
+}
+
+// 64 MiB и 3 прохода — около 250 мс на типичном настольном процессоре
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+// Ключ данных, зашифрованный ключом из мастер-пароля и ключом восстановления
+#[derive(Clone, Zeroize)]
+struct KeySlots {
+    password: [u8; SLOT_LEN],
+    #[zeroize(skip)]
+    recovery_params: KdfParams,
+    recovery_salt: [u8; SALT_LEN],
+    recovery: [u8; SLOT_LEN],
+}
+
+impl KeySlots {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.password);
+        self.recovery_params.write(out);
+        out.extend_from_slice(&self.recovery_salt);
+        out.extend_from_slice(&self.recovery);
+    }
+
+    fn read(data: &[u8]) -> Self {
+        let mut slots = Self {
+            password: [0u8; SLOT_LEN],
+            recovery_params: KdfParams::read(&data[SLOT_LEN..]),
+            recovery_salt: [0u8; SALT_LEN],
+            recovery: [0u8; SLOT_LEN],
+        };
+        let salt_start = SLOT_LEN + PARAMS_LEN;
+        slots.password.copy_from_slice(&data[..SLOT_LEN]);
+        slots.recovery_salt.copy_from_slice(&data[salt_start..salt_start + SALT_LEN]);
+        slots.recovery.copy_from_slice(&data[salt_start + SALT_LEN..SLOTS_LEN]);
+        slots
+    }
+}
+
+// Ключ затирается при drop, в том числе когда хранилище блокируется.
+// Без слотов ключ выведен прямо из пароля (версия 2), со слотами — это
+// случайный ключ данных, а соль и параметры относятся к слоту пароля
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct VaultKey {
+    key: [u8; KEY_LEN],
+    salt: [u8; SALT_LEN],
+    #[zeroize(skip)]
+    params: KdfParams,
+    slots: Option<KeySlots>,
+}
+
+impl VaultKey {
+    pub fn params(&self) -> KdfParams {
+        self.params
+    }
+}
+
+// Разобранный заголовок: с чем выводить ключ и где начинается nonce
+pub struct Header {
+    pub params: KdfParams,
+    pub salt: [u8; SALT_LEN],
+    slots: Option<KeySlots>,
+    nonce_start: usize,
+}
+
+impl Header {
+    pub fn has_recovery_key(&self) -> bool {
+        self.slots.is_some()
+    }
+}
+
+// Сравнение секретов без раннего выхода на первом несовпавшем байте,
+// чтобы время ответа не подсказывало, сколько символов угадано.
+// Раскрывается только то, совпадают ли длины
+pub fn secrets_equal(a: &str, b: &str) -> bool {
+    a.as_bytes().ct_eq(b.as_bytes()).into()
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(VAULT_MAGIC)
+}
+
+pub fn generate_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+pub fn read_header(data: &[u8]) -> Result<Header, StoreError> {
+    let truncated = || StoreError::Crypto("Vault file header is truncated or invalid".to_string());
+    if !is_encrypted(data) || data.len() <= VAULT_MAGIC.len() {
+        return Err(truncated());
+    }
+
+    let mut offset = VAULT_MAGIC.len() + 1;
+    let version = data[VAULT_MAGIC.len()];
+    let params = match version {
+        LEGACY_VAULT_VERSION => KdfParams::LEGACY,
+        VAULT_VERSION | RECOVERY_VAULT_VERSION => {
+            let params = data.get(offset..offset + PARAMS_LEN).ok_or_else(truncated)?;
+            offset += PARAMS_LEN;
+            KdfParams::read(params)
+        }
+        version => {
+            return Err(StoreError::Crypto(format!("Unsupported vault format version: {}", version)));
+        }
+    };
+
+    let mut salt = [0u8; SALT_LEN];
+    salt.copy_from_slice(data.get(offset..offset + SALT_LEN).ok_or_else(truncated)?);
+    offset += SALT_LEN;
+
+    let mut slots = None;
+    if version == RECOVERY_VAULT_VERSION {
+        slots = Some(KeySlots::read(data.get(offset..offset + SLOTS_LEN).ok_or_else(truncated)?));
+        offset += SLOTS_LEN;
+    }
+
+    let nonce_start = offset;
+    if data.len() < nonce_start + NONCE_LEN {
+        return Err(truncated());
+    }
+
+    Ok(Header { params, salt, slots, nonce_start })
+}
+
+pub fn derive_key(master_password: &str, params: KdfParams, salt: [u8; SALT_LEN]) -> Result<VaultKey, StoreError> {
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params.to_argon2()?);
+
+    let mut key = VaultKey {
+        key: [0u8; KEY_LEN],
+        salt,
+        params,
+        slots: None,
+    };
+    argon2
+        .hash_password_into(master_password.as_bytes(), &salt, &mut key.key)
+        .map_err(|e| StoreError::Crypto(format!("Failed to derive key: {}", e)))?;
+
+    Ok(key)
+}
+
+// Ключ под параметры и соль из заголовка существующего файла
+pub fn derive_key_for(master_password: &str, data: &[u8]) -> Result<VaultKey, StoreError> {
+    let header = read_header(data)?;
+    let key = derive_key(master_password, header.params, header.salt)?;
+    match header.slots {
+        None => Ok(key),
+        Some(slots) => open_slot(&key, &slots.password, header.params, header.salt, slots.clone()),
+    }
+}
+
+// То же по коду восстановления; у файла без слотов его нет
+pub fn derive_recovery_key_for(recovery_key: &str, data: &[u8]) -> Result<VaultKey, StoreError> {
+    let header = read_header(data)?;
+    let slots = header
+        .slots
+        .ok_or_else(|| StoreError::Validation("Vault has no recovery key".to_string()))?;
+    let recovery = derive_key(&normalize_recovery_key(recovery_key), slots.recovery_params, slots.recovery_salt)?;
+    open_slot(&recovery, &slots.recovery, header.params, header.salt, slots.clone())
+}
+
+// Случайный код для записи на бумаге, вида XXXX-XXXX-...
+pub fn generate_recovery_code() -> Zeroizing<String> {
+    let mut bytes = Zeroizing::new([0u8; RECOVERY_KEY_BYTES]);
+    OsRng.fill_bytes(bytes.as_mut());
+    let encoded = Zeroizing::new(data_encoding::BASE32_NOPAD.encode(bytes.as_ref()));
+    let groups: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(RECOVERY_GROUP_LEN)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    Zeroizing::new(groups.join("-"))
+}
+
+// Дефисы, пробелы и регистр при вводе кода не важны
+fn normalize_recovery_key(recovery_key: &str) -> Zeroizing<String> {
+    Zeroizing::new(
+        recovery_key
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_uppercase())
+            .collect(),
+    )
+}
+
+fn seal_slot(wrapping: &VaultKey, data_key: &[u8; KEY_LEN]) -> Result<[u8; SLOT_LEN], StoreError> {
+    let cipher = XChaCha20Poly1305::new((&wrapping.key).into());
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let sealed = cipher
+        .encrypt(XNonce::from_slice(&nonce), data_key.as_slice())
+        .map_err(|_| StoreError::Crypto("Failed to wrap vault key".to_string()))?;
+
+    let mut slot = [0u8; SLOT_LEN];
+    slot[..NONCE_LEN].copy_from_slice(&nonce);
+    slot[NONCE_LEN..].copy_from_slice(&sealed);
+    Ok(slot)
+}
+
+fn open_slot(
+    wrapping: &VaultKey,
+    slot: &[u8; SLOT_LEN],
+    params: KdfParams,
+    salt: [u8; SALT_LEN],
+    slots: KeySlots,
+) -> Result<VaultKey, StoreError> {
+    let cipher = XChaCha20Poly1305::new((&wrapping.key).into());
+    let data_key = Zeroizing::new(
+        cipher
+            .decrypt(XNonce::from_slice(&slot[..NONCE_LEN]), &slot[NONCE_LEN..])
+            .map_err(|_| StoreError::IncorrectPassword)?,
+    );
+
+    let mut key = VaultKey {
+        key: [0u8; KEY_LEN],
+        salt,
+        params,
+        slots: Some(slots),
+    };
+    key.key.copy_from_slice(&data_key);
+    Ok(key)
+}
+
+// Новый случайный ключ данных, доступный и по паролю, и по коду восстановления.
+// Прежний код восстановления, если был, перестает действовать
+pub fn with_recovery_key(master_password: &str, params: KdfParams, recovery_key: &str) -> Result<VaultKey, StoreError> {
+    let mut data_key = Zeroizing::new([0u8; KEY_LEN]);
+    OsRng.fill_bytes(data_key.as_mut());
+
+    let salt = generate_salt();
+    let password = derive_key(master_password, params, salt)?;
+    let recovery_salt = generate_salt();
+    let recovery = derive_key(&normalize_recovery_key(recovery_key), params, recovery_salt)?;
+
+    Ok(VaultKey {
+        key: *data_key,
+        salt,
+        params,
+        slots: Some(KeySlots {
+            password: seal_slot(&password, &data_key)?,
+            recovery_params: params,
+            recovery_salt,
+            recovery: seal_slot(&recovery, &data_key)?,
+        }),
+    })
+}
+
+// Ключ под новый пароль. Если есть слот восстановления, ключ данных
+// сохраняется и перешифровывается только слот пароля, чтобы код
+// восстановления продолжал работать после смены мастер-пароля
+pub fn rewrap(key: &VaultKey, new_password: &str, params: KdfParams) -> Result<VaultKey, StoreError> {
+    let salt = generate_salt();
+    let password = derive_key(new_password, params, salt)?;
+    let Some(slots) = &key.slots else {
+        return Ok(password);
+    };
+
+    Ok(VaultKey {
+        key: key.key,
+        salt,
+        params,
+        slots: Some(KeySlots {
+            password: seal_slot(&password, &key.key)?,
+            ..slots.clone()
+        }),
+    })
+}
+
+pub fn encrypt(key: &VaultKey, plaintext: &[u8]) -> Result<Vec<u8>, StoreError> {
+    let cipher = XChaCha20Poly1305::new((&key.key).into());
+
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .map_err(|_| StoreError::Crypto("Failed to encrypt passwords".to_string()))?;
+
+    let header_len = VAULT_MAGIC.len() + 1 + PARAMS_LEN + SALT_LEN + SLOTS_LEN + NONCE_LEN;
+    let mut out = Vec::with_capacity(header_len + ciphertext.len());
+    out.extend_from_slice(VAULT_MAGIC);
+    out.push(if key.slots.is_some() { RECOVERY_VAULT_VERSION } else { VAULT_VERSION });
+    key.params.write(&mut out);
+    out.extend_from_slice(&key.salt);
+    if let Some(slots) = &key.slots {
+        slots.write(&mut out);
+    }
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+pub fn decrypt(key: &VaultKey, data: &[u8]) -> Result<Zeroizing<Vec<u8>>, StoreError> {
+    let header = read_header(data)?;
+
+    let ciphertext_start = header.nonce_start + NONCE_LEN;
+    let nonce = XNonce::from_slice(&data[header.nonce_start..ciphertext_start]);
+    let cipher = XChaCha20Poly1305::new((&key.key).into());
+
+    // Мастер-пароль нигде не сравнивается как строка и не хранится: проверкой
+    // служит тег Poly1305, который AEAD сверяет в постоянное время.
+    // Ошибка проверки тега означает неверный ключ (или поврежденный файл)
+    cipher
+        .decrypt(nonce, &data[ciphertext_start..])
+        .map(Zeroizing::new)
+        .map_err(|_| StoreError::IncorrectPassword)
+}
+
+// Случайный ключ режима ленивой расшифровки. Живет только в памяти
+// процесса и до перезапуска, на диск не попадает
+pub type MemoryKey = Zeroizing<[u8; KEY_LEN]>;
+
+pub fn generate_memory_key() -> MemoryKey {
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    OsRng.fill_bytes(key.as_mut());
+    key
+}
+
+// nonce | шифротекст, без заголовка: результат не покидает процесс
+pub fn seal_in_memory(key: &MemoryKey, plaintext: &[u8]) -> Result<Vec<u8>, StoreError> {
+    let cipher = XChaCha20Poly1305::new(key.as_ref().into());
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .map_err(|_| StoreError::Crypto("Failed to seal entry".to_string()))?;
+
+    let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+pub fn open_in_memory(key: &MemoryKey, sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>, StoreError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(StoreError::Crypto("Sealed entry is truncated".to_string()));
+    }
+    let cipher = XChaCha20Poly1305::new(key.as_ref().into());
+    cipher
+        .decrypt(XNonce::from_slice(&sealed[..NONCE_LEN]), &sealed[NONCE_LEN..])
+        .map(Zeroizing::new)
+        .map_err(|_| StoreError::Crypto("Failed to open sealed entry".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_kdf_params_are_stored_in_the_header() {
+        let params = KdfParams { memory_kib: 256, iterations: 3, parallelism: 2 };
+        let key = derive_key("master", params, generate_salt()).unwrap();
+        assert_eq!(key.params(), params);
+        let data = encrypt(&key, b"entries").unwrap();
+
+        let header = read_header(&data).unwrap();
+        assert_eq!(header.params, params);
+        assert_eq!(header.salt, key.salt);
+
+        let reopened = derive_key_for("master", &data).unwrap();
+        assert_eq!(reopened.params(), params);
+        assert_eq!(decrypt(&reopened, &data).unwrap().as_slice(), b"entries");
+        assert!(matches!(
+            decrypt(&derive_key_for("other", &data).unwrap(), &data),
+            Err(StoreError::IncorrectPassword)
+        ));
+    }
+
+    #[test]
+    fn oversized_kdf_params_are_rejected() {
+        let params = KdfParams { memory_kib: MAX_MEMORY_KIB + 1, iterations: 1, parallelism: 1 };
+        assert!(matches!(params.validate(), Err(StoreError::Validation(_))));
+    }
+}
@@ -0,0 +1,86 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum StoreError {
+    Locked,
+    IncorrectPassword,
+    AlreadyOpen,
+    // Слишком много неудачных попыток; сколько секунд ждать до следующей
+    TooManyAttempts(u64),
+    // Файл хранилища не читается и отложен в сторону; в строке путь к нему
+    Corrupt(String),
+    // Сетевой запрос не уложился в таймаут
+    Timeout,
+    // Запись помечена require_reauth, а подтверждения мастер-пароля нет или оно истекло
+    ReauthRequired,
+    // Генератор не смог выполнить ограничения; в строке причина для пользователя
+    GenerationFailed(String),
+    NotFound(String),
+    Validation(String),
+    Io(String),
+    Serialize(String),
+    Crypto(String),
+}
+
+impl StoreError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StoreError::Locked => "Locked",
+            StoreError::IncorrectPassword => "IncorrectPassword",
+            StoreError::AlreadyOpen => "AlreadyOpen",
+            StoreError::TooManyAttempts(_) => "TooManyAttempts",
+            StoreError::Corrupt(_) => "Corrupt",
+            StoreError::Timeout => "Timeout",
+            StoreError::ReauthRequired => "ReauthRequired",
+            StoreError::GenerationFailed(_) => "GenerationFailed",
+            StoreError::NotFound(_) => "NotFound",
+            StoreError::Validation(_) => "Validation",
+            StoreError::Io(_) => "Io",
+            StoreError::Serialize(_) => "Serialize",
+            StoreError::Crypto(_) => "Crypto",
+        }
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Locked => write!(f, "Password store is locked"),
+            StoreError::IncorrectPassword => write!(f, "Incorrect master password"),
+            StoreError::AlreadyOpen => write!(f, "Vault is already open in another process"),
+            StoreError::TooManyAttempts(secs) => {
+                write!(f, "Too many failed unlock attempts, try again in {} seconds", secs)
+            }
+            StoreError::Corrupt(path) => write!(
+                f,
+                "Vault file could not be read and was moved to {}; restore a backup or dismiss to start a new vault",
+                path
+            ),
+            StoreError::Timeout => write!(f, "Network request timed out"),
+            StoreError::ReauthRequired => write!(f, "Confirm the master password to reveal this entry"),
+            StoreError::GenerationFailed(reason) => write!(f, "Could not generate a password: {}", reason),
+            StoreError::NotFound(msg)
+            | StoreError::Validation(msg)
+            | StoreError::Io(msg)
+            | StoreError::Serialize(msg)
+            | StoreError::Crypto(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+// Фронтенд получает { "kind": "...", "message": "..." } и ветвится по kind;
+// для TooManyAttempts добавляется retry_after_secs
+impl Serialize for StoreError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("StoreError", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let StoreError::TooManyAttempts(secs) = self {
+            state.serialize_field("retry_after_secs", secs)?;
+        }
+        state.end()
+    }
+}
@@ -0,0 +1,34 @@
+use serde::Serialize;
+
+// События для окон фронтенда, чтобы они не опрашивали бэкенд
+pub const STORE_CHANGED: &str = "store-changed";
+pub const STORE_LOCKED: &str = "store-locked";
+pub const STORE_UNLOCKED: &str = "store-unlocked";
+pub const VAULT_SWITCHED: &str = "vault-switched";
+// Время показа пароля из reveal_password истекло, интерфейс должен его скрыть
+pub const PASSWORD_HIDE: &str = "password-hide";
+// Хранилище не открылось и работает только в памяти: изменения не сохраняются
+pub const STORE_DEGRADED: &str = "store-degraded";
+
+// Пустой список ids означает, что могло измениться все хранилище
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreChanged {
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordHide {
+    pub reveal_token: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreDegraded {
+    pub vault: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultSwitched {
+    pub name: String,
+}
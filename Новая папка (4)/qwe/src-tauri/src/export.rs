@@ -0,0 +1,69 @@
+use crate::error::StoreError;
+use crate::import::CsvLayout;
+use crate::PasswordEntry;
+
+// Экранирование запятых, кавычек и переводов строк выполняет csv::Writer
+pub fn to_csv(entries: &[&PasswordEntry], layout: &CsvLayout) -> Result<Vec<u8>, StoreError> {
+    let mut writer = csv::WriterBuilder::new()
+        .quote_style(csv::QuoteStyle::Necessary)
+        .from_writer(Vec::new());
+
+    writer
+        .write_record([layout.name, layout.username, layout.password, layout.url, layout.notes])
+        .map_err(|e| StoreError::Serialize(format!("Failed to write CSV header: {}", e)))?;
+
+    for entry in entries {
+        writer
+            .write_record([
+                entry.name.as_str(),
+                entry.username.as_str(),
+                entry.password.as_str(),
+                entry.url.as_deref().unwrap_or(""),
+                entry.notes.as_deref().unwrap_or(""),
+            ])
+            .map_err(|e| StoreError::Serialize(format!("Failed to write CSV row: {}", e)))?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| StoreError::Serialize(format!("Failed to finish CSV: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{self, MergeStrategy, GENERIC_LAYOUT};
+    use std::collections::HashMap;
+
+    #[test]
+    fn csv_round_trip_reproduces_entries() {
+        let entries = [
+            PasswordEntry::new(
+                "Mail, personal".to_string(),
+                "alice".to_string(),
+                "pa\"ss,word".to_string(),
+                Some("https://mail.example.com".to_string()),
+                Some("first line\nsecond \"quoted\" line".to_string()),
+            ),
+            PasswordEntry::new("Bank".to_string(), String::new(), "1234".to_string(), None, None),
+        ];
+        let exported = to_csv(&entries.iter().collect::<Vec<_>>(), &GENERIC_LAYOUT).unwrap();
+
+        let parsed = import::parse_csv(exported.as_slice(), &GENERIC_LAYOUT).unwrap();
+        let mut store = HashMap::new();
+        let summary = import::merge(&mut store, parsed, MergeStrategy::KeepBoth, 5);
+        assert_eq!(summary.imported, entries.len());
+        assert!(summary.errors.is_empty());
+
+        for original in &entries {
+            let imported = store
+                .values()
+                .find(|entry| entry.name == original.name)
+                .unwrap();
+            assert_eq!(imported.username, original.username);
+            assert_eq!(imported.password, original.password);
+            assert_eq!(imported.url, original.url);
+            assert_eq!(imported.notes, original.notes);
+        }
+    }
+}
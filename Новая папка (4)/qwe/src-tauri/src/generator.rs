@@ -0,0 +1,464 @@
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::error::StoreError;
+
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*()";
+const AMBIGUOUS: &str = "O0l1I";
+const DEFAULT_CHARSET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!@#$%^&*()";
+
+// Длины вне этих границ приводятся к ближайшей. Верхняя граница рассчитана
+// на ключи API и WiFi; генерация линейна по длине, набор символов собирается один раз
+pub const MIN_LENGTH: usize = 8;
+pub const MAX_LENGTH: usize = 128;
+pub const DEFAULT_LENGTH: usize = 16;
+// Сколько раз перегенерировать пароль, отвергнутый no_repeats/no_sequences
+const PATTERN_ATTEMPTS: usize = 1000;
+// Длина отвергаемых повторов и последовательностей
+const PATTERN_RUN: usize = 3;
+
+// Границы длины для интерфейса, чтобы ползунок не расходился с бэкендом
+#[derive(Debug, Serialize)]
+pub struct GenerationLimits {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub default_length: usize,
+}
+
+pub const LIMITS: GenerationLimits = GenerationLimits {
+    min_length: MIN_LENGTH,
+    max_length: MAX_LENGTH,
+    default_length: DEFAULT_LENGTH,
+};
+
+// Энтропия считается по размеру алфавита (или словаря) и длине результата
+#[derive(Serialize, Zeroize, ZeroizeOnDrop)]
+pub struct GeneratedPassword {
+    pub value: String,
+    pub entropy_bits: f64,
+}
+
+impl fmt::Debug for GeneratedPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeneratedPassword")
+            .field("value", &crate::REDACTED)
+            .field("entropy_bits", &self.entropy_bits)
+            .finish()
+    }
+}
+
+fn entropy(pool: usize, count: usize) -> f64 {
+    count as f64 * (pool as f64).log2()
+}
+
+// Все генераторы берут случайность напрямую из ОС (getrandom), как и crypto.rs
+// для соли и nonce, без промежуточного пользовательского ГПСЧ.
+// Исключение — generate_with_rng, куда ГПСЧ передается явно
+#[derive(Debug, Deserialize)]
+pub struct GeneratorOptions {
+    pub uppercase: bool,
+    pub lowercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    #[serde(default)]
+    pub exclude_ambiguous: bool,
+    // Без трех одинаковых символов подряд ("aaa")
+    #[serde(default)]
+    pub no_repeats: bool,
+    // Без трех символов с соседними кодами подряд ("abc", "321")
+    #[serde(default)]
+    pub no_sequences: bool,
+}
+
+impl GeneratorOptions {
+    fn classes(&self) -> Vec<Vec<char>> {
+        [
+            (self.uppercase, UPPERCASE),
+            (self.lowercase, LOWERCASE),
+            (self.digits, DIGITS),
+            (self.symbols, SYMBOLS),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, class)| {
+            class
+                .chars()
+                .filter(|c| !(self.exclude_ambiguous && AMBIGUOUS.contains(*c)))
+                .collect()
+        })
+        .collect()
+    }
+
+    // Проверяется пароль целиком, поэтому "a1b" не последовательность,
+    // а "xyz" — да, из каких бы классов ни были символы
+    fn accepts(&self, password: &[char]) -> bool {
+        password.windows(PATTERN_RUN).all(|run| {
+            let steps: Vec<i64> = run.windows(2).map(|pair| pair[1] as i64 - pair[0] as i64).collect();
+            let repeated = steps.iter().all(|step| *step == 0);
+            let sequential = steps.iter().all(|step| *step == 1) || steps.iter().all(|step| *step == -1);
+            !(self.no_repeats && repeated || self.no_sequences && sequential)
+        })
+    }
+}
+
+// Набор собирается в Vec<char> один раз: индекс по символам, а не по байтам,
+// и gen_range дает равномерное распределение по всему набору
+pub fn generate_default(length: usize) -> GeneratedPassword {
+    let charset: Vec<char> = DEFAULT_CHARSET.chars().collect();
+    let length = length.clamp(MIN_LENGTH, MAX_LENGTH);
+
+    let mut rng = OsRng;
+    GeneratedPassword {
+        value: (0..length)
+            .map(|_| charset[rng.gen_range(0..charset.len())])
+            .collect(),
+        entropy_bits: entropy(charset.len(), length),
+    }
+}
+
+pub fn generate(length: usize, options: &GeneratorOptions) -> Result<GeneratedPassword, StoreError> {
+    generate_with_rng(&mut OsRng, length, options)
+}
+
+// Источник случайности передается снаружи, чтобы генерацию можно было
+// воспроизвести с детерминированным ГПСЧ; вне тестов сюда попадает только OsRng
+pub(crate) fn generate_with_rng<R: Rng>(
+    rng: &mut R,
+    length: usize,
+    options: &GeneratorOptions,
+) -> Result<GeneratedPassword, StoreError> {
+    let classes = options.classes();
+    if classes.is_empty() {
+        return Err(StoreError::GenerationFailed("no character classes enabled".to_string()));
+    }
+
+    let length = length.clamp(MIN_LENGTH, MAX_LENGTH);
+    let charset: Vec<char> = classes.concat();
+
+    for _ in 0..PATTERN_ATTEMPTS {
+        // По одному символу из каждого включенного класса, остальное из общего набора
+        let mut password: Vec<char> = classes
+            .iter()
+            .map(|class| class[rng.gen_range(0..class.len())])
+            .collect();
+        while password.len() < length {
+            password.push(charset[rng.gen_range(0..charset.len())]);
+        }
+        password.shuffle(rng);
+
+        if options.accepts(&password) {
+            return Ok(GeneratedPassword {
+                value: password.iter().collect(),
+                entropy_bits: entropy(charset.len(), length),
+            });
+        }
+        password.zeroize();
+    }
+
+    Err(StoreError::GenerationFailed(format!(
+        "no password without repeated or sequential characters after {} tries; enable more character classes",
+        PATTERN_ATTEMPTS
+    )))
+}
+
+// Настройки generate_password по умолчанию. Лежат в config.json рядом с
+// хранилищем, а не в нем, поэтому действуют и до разблокировки
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationSettings {
+    pub length: usize,
+    pub uppercase: bool,
+    pub lowercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub exclude_ambiguous: bool,
+}
+
+impl Default for GenerationSettings {
+    fn default() -> Self {
+        Self {
+            length: DEFAULT_LENGTH,
+            uppercase: true,
+            lowercase: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+        }
+    }
+}
+
+impl GenerationSettings {
+    pub fn options(&self) -> GeneratorOptions {
+        GeneratorOptions {
+            uppercase: self.uppercase,
+            lowercase: self.lowercase,
+            digits: self.digits,
+            symbols: self.symbols,
+            exclude_ambiguous: self.exclude_ambiguous,
+            no_repeats: false,
+            no_sequences: false,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), StoreError> {
+        if !(MIN_LENGTH..=MAX_LENGTH).contains(&self.length) {
+            return Err(StoreError::Validation(format!(
+                "length: must be between {} and {}",
+                MIN_LENGTH, MAX_LENGTH
+            )));
+        }
+        if self.options().classes().is_empty() {
+            return Err(StoreError::Validation("At least one character class must be enabled".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// Правила сайта: допустимая длина, обязательные классы и запрещенные символы.
+// Хранится в записи, чтобы при смене пароля новый тоже им соответствовал
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Zeroize)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    #[serde(default)]
+    pub require_uppercase: bool,
+    #[serde(default)]
+    pub require_lowercase: bool,
+    #[serde(default)]
+    pub require_digits: bool,
+    #[serde(default)]
+    pub require_symbols: bool,
+    #[serde(default)]
+    pub forbidden: String,
+}
+
+impl PasswordPolicy {
+    fn allowed(&self, class: &str) -> Vec<char> {
+        class.chars().filter(|c| !self.forbidden.contains(*c)).collect()
+    }
+
+    // Длина выбирается максимально допустимой в пределах MAX_LENGTH
+    fn length(&self) -> usize {
+        self.max_length.min(MAX_LENGTH)
+    }
+
+    // Проверяет, что политике вообще можно удовлетворить, и возвращает
+    // обязательные классы и общий набор символов
+    fn prepare(&self) -> Result<(Vec<Vec<char>>, Vec<char>), StoreError> {
+        if self.min_length == 0 || self.min_length > self.max_length {
+            return Err(StoreError::Validation("policy: invalid length range".to_string()));
+        }
+        if self.min_length > MAX_LENGTH {
+            return Err(StoreError::Validation(format!(
+                "policy: passwords longer than {} characters are not supported",
+                MAX_LENGTH
+            )));
+        }
+
+        let mut required = Vec::new();
+        for (enabled, class, name) in [
+            (self.require_uppercase, UPPERCASE, "uppercase"),
+            (self.require_lowercase, LOWERCASE, "lowercase"),
+            (self.require_digits, DIGITS, "digits"),
+            (self.require_symbols, SYMBOLS, "symbols"),
+        ] {
+            if !enabled {
+                continue;
+            }
+            let allowed = self.allowed(class);
+            if allowed.is_empty() {
+                return Err(StoreError::Validation(format!("policy: all {} are forbidden", name)));
+            }
+            required.push(allowed);
+        }
+        if required.len() > self.length() {
+            return Err(StoreError::Validation("policy: too many required classes for the length".to_string()));
+        }
+
+        let charset = self.allowed(DEFAULT_CHARSET);
+        if charset.is_empty() {
+            return Err(StoreError::Validation("policy: all characters are forbidden".to_string()));
+        }
+        Ok((required, charset))
+    }
+
+    pub fn validate(&self) -> Result<(), StoreError> {
+        self.prepare().map(|_| ())
+    }
+}
+
+// При сохранении политики ее ошибки — это ошибки ввода (Validation),
+// а при генерации по ней — причина, по которой пароль не получился
+pub fn generate_for_policy(policy: &PasswordPolicy) -> Result<GeneratedPassword, StoreError> {
+    let (required, charset) = policy.prepare().map_err(|e| match e {
+        StoreError::Validation(reason) => StoreError::GenerationFailed(reason),
+        e => e,
+    })?;
+    let length = policy.length();
+    let mut rng = OsRng;
+
+    let mut password: Vec<char> = required
+        .iter()
+        .map(|class| class[rng.gen_range(0..class.len())])
+        .collect();
+    while password.len() < length {
+        password.push(charset[rng.gen_range(0..charset.len())]);
+    }
+    password.shuffle(&mut rng);
+
+    Ok(GeneratedPassword {
+        value: password.into_iter().collect(),
+        entropy_bits: entropy(charset.len(), length),
+    })
+}
+
+// Большой список EFF (7776 слов, по строке "номер<TAB>слово"), вшитый в бинарник
+const EFF_WORDLIST: &str = include_str!("eff_large_wordlist.txt");
+
+pub const DEFAULT_PASSPHRASE_WORDS: u8 = 4;
+const MIN_PASSPHRASE_WORDS: u8 = 3;
+const MAX_PASSPHRASE_WORDS: u8 = 12;
+
+fn wordlist() -> Vec<&'static str> {
+    EFF_WORDLIST
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .collect()
+}
+
+pub fn generate_passphrase(
+    words: u8,
+    separator: &str,
+    capitalize: bool,
+    add_number: bool,
+) -> Result<GeneratedPassword, StoreError> {
+    // Ошибки генераторов — GenerationFailed, как и в generate
+    if !(MIN_PASSPHRASE_WORDS..=MAX_PASSPHRASE_WORDS).contains(&words) {
+        return Err(StoreError::GenerationFailed(format!(
+            "words: must be between {} and {}",
+            MIN_PASSPHRASE_WORDS, MAX_PASSPHRASE_WORDS
+        )));
+    }
+
+    let list = wordlist();
+    let mut rng = OsRng;
+    let mut chosen: Vec<String> = (0..words)
+        .map(|_| {
+            let word = list[rng.gen_range(0..list.len())];
+            if capitalize {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            } else {
+                word.to_string()
+            }
+        })
+        .collect();
+
+    let mut entropy_bits = entropy(list.len(), words as usize);
+
+    // Цифра дописывается к случайно выбранному слову
+    if add_number {
+        let index = rng.gen_range(0..chosen.len());
+        let digit = rng.gen_range(0..10);
+        chosen[index].push_str(&digit.to_string());
+        entropy_bits += entropy(10 * chosen.len(), 1);
+    }
+
+    Ok(GeneratedPassword {
+        value: chosen.join(separator),
+        entropy_bits,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::collections::HashMap;
+
+    #[test]
+    fn default_charset_is_used_uniformly() {
+        let charset: Vec<char> = DEFAULT_CHARSET.chars().collect();
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        let mut total = 0;
+        for _ in 0..2000 {
+            let generated = generate_default(DEFAULT_LENGTH);
+            for c in generated.value.chars() {
+                *counts.entry(c).or_default() += 1;
+                total += 1;
+            }
+        }
+
+        assert!(counts.keys().all(|c| charset.contains(c)));
+        // Около 444 на символ; границы отстоят от ожидания на 8 сигм
+        let expected = total / charset.len();
+        for c in &charset {
+            let count = counts.get(c).copied().unwrap_or(0);
+            assert!(
+                count > expected * 6 / 10 && count < expected * 14 / 10,
+                "'{}' appeared {} times, expected about {}",
+                c,
+                count,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn every_enabled_class_appears() {
+        let options = GenerationSettings::default().options();
+        for _ in 0..500 {
+            let generated = generate(MIN_LENGTH, &options).unwrap();
+            for class in [UPPERCASE, LOWERCASE, DIGITS, SYMBOLS] {
+                assert!(generated.value.chars().any(|c| class.contains(c)));
+            }
+        }
+    }
+
+    #[test]
+    fn seeded_generation_is_reproducible() {
+        let options = GenerationSettings::default().options();
+        let generated = generate_with_rng(&mut ChaCha8Rng::seed_from_u64(42), DEFAULT_LENGTH, &options).unwrap();
+        assert_eq!(generated.value, "W&MRypy4R8XL0kF3");
+    }
+
+    #[test]
+    fn seeded_generation_respects_length_and_classes() {
+        let options = GenerationSettings::default().options();
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        for length in [MIN_LENGTH, DEFAULT_LENGTH, MAX_LENGTH] {
+            let generated = generate_with_rng(&mut rng, length, &options).unwrap();
+            assert_eq!(generated.value.chars().count(), length);
+            assert!(generated.value.chars().all(|c| DEFAULT_CHARSET.contains(c)));
+            for class in [UPPERCASE, LOWERCASE, DIGITS, SYMBOLS] {
+                assert!(generated.value.chars().any(|c| class.contains(c)));
+            }
+        }
+        // Длина вне границ приводится к ближайшей
+        let short = generate_with_rng(&mut rng, 1, &options).unwrap();
+        assert_eq!(short.value.chars().count(), MIN_LENGTH);
+    }
+
+    #[test]
+    fn passphrase_errors_are_generation_failures() {
+        for words in [MIN_PASSPHRASE_WORDS - 1, MAX_PASSPHRASE_WORDS + 1] {
+            assert!(matches!(
+                generate_passphrase(words, "-", false, false),
+                Err(StoreError::GenerationFailed(_))
+            ));
+        }
+        let generated = generate_passphrase(DEFAULT_PASSPHRASE_WORDS, " ", false, true).unwrap();
+        assert_eq!(generated.value.split(' ').count(), DEFAULT_PASSPHRASE_WORDS as usize);
+    }
+}
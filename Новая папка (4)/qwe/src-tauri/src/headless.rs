@@ -0,0 +1,63 @@
+use std::env;
+use std::fs;
+use zeroize::Zeroizing;
+
+// Разблокировка без диалога для киосков и автоматизации. Работает только
+// с флагом HEADLESS_FLAG в командной строке: без него переменная и файл
+// игнорируются, чтобы случайно оставленный пароль в окружении ничего не открыл.
+// Это слабее ввода вручную: пароль виден процессам того же пользователя
+// (окружение, /proc) и лежит в файле, поэтому его стоит передавать через
+// дескриптор (--password-file /dev/fd/3) или файл с правами 0600
+const MASTER_PASSWORD_ENV: &str = "VAULT_MASTER_PASSWORD";
+const HEADLESS_FLAG: &str = "--headless-unlock";
+const PASSWORD_FILE_ARG: &str = "--password-file";
+
+fn password_file(args: &[String]) -> Option<String> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == PASSWORD_FILE_ARG {
+            return args.next().cloned();
+        }
+        if let Some(path) = arg.strip_prefix(PASSWORD_FILE_ARG).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+// Мастер-пароль из файла или переменной окружения. Переменная удаляется
+// из окружения процесса сразу после чтения, чтобы ее не унаследовали
+// дочерние процессы
+pub fn master_password() -> Option<Zeroizing<String>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let from_env = env::var(MASTER_PASSWORD_ENV).ok().map(Zeroizing::new);
+    if from_env.is_some() {
+        env::remove_var(MASTER_PASSWORD_ENV);
+    }
+    let file = password_file(&args);
+
+    if !args.iter().any(|arg| arg == HEADLESS_FLAG) {
+        if from_env.is_some() || file.is_some() {
+            log::warn!("Master password supplied without {}, ignoring it", HEADLESS_FLAG);
+        }
+        return None;
+    }
+
+    if let Some(path) = file {
+        return match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let contents = Zeroizing::new(contents);
+                // Завершающий перевод строки от echo и редакторов паролем не считается
+                Some(Zeroizing::new(contents.trim_end_matches(['\r', '\n']).to_string()))
+            }
+            Err(e) => {
+                log::error!("Failed to read password file {}: {}", path, e);
+                None
+            }
+        };
+    }
+    if from_env.is_none() {
+        log::warn!("{} given, but neither {} nor {} is set", HEADLESS_FLAG, MASTER_PASSWORD_ENV, PASSWORD_FILE_ARG);
+    }
+    from_env
+}
@@ -0,0 +1,374 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+use crate::error::StoreError;
+use crate::{crypto, schema, CustomField, PasswordEntry};
+
+// Названия колонок CSV, из которых берутся поля записи
+pub struct CsvLayout {
+    pub name: &'static str,
+    pub username: &'static str,
+    pub password: &'static str,
+    pub url: &'static str,
+    pub notes: &'static str,
+}
+
+pub const GENERIC_LAYOUT: CsvLayout = CsvLayout {
+    name: "name",
+    username: "username",
+    password: "password",
+    url: "url",
+    notes: "notes",
+};
+
+// Экспорт KeePass/KeePassXC
+pub const KEEPASS_LAYOUT: CsvLayout = CsvLayout {
+    name: "Account",
+    username: "Login Name",
+    password: "Password",
+    url: "Web Site",
+    notes: "Comments",
+};
+
+// Что делать, если в хранилище уже есть запись с тем же name и username
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum MergeStrategy {
+    // Оставить существующую запись
+    Skip,
+    // Заменить данные существующей записи импортированными
+    Overwrite,
+    // Добавить импортированную запись рядом с существующей
+    #[default]
+    KeepBoth,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub overwritten: usize,
+    // Пропущены из-за существующей записи при Skip
+    pub kept_existing: usize,
+    pub skipped: usize,
+    pub duplicates: usize,
+    pub errors: Vec<String>,
+    // Сколько элементов каждого типа было в файле (для форматов с типами, например Bitwarden)
+    pub item_types: BTreeMap<String, usize>,
+}
+
+#[derive(Default)]
+pub struct ParsedImport {
+    pub entries: Vec<PasswordEntry>,
+    pub errors: Vec<String>,
+    pub item_types: BTreeMap<String, usize>,
+}
+
+fn column(headers: &csv::StringRecord, name: &str) -> Option<usize> {
+    headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name))
+}
+
+fn optional(value: Option<&str>) -> Option<String> {
+    value
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_string())
+}
+
+// Битые строки не прерывают импорт, а попадают в список ошибок
+pub fn parse_csv<R: Read>(reader: R, layout: &CsvLayout) -> Result<ParsedImport, StoreError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(reader);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| StoreError::Serialize(format!("Failed to read CSV header: {}", e)))?
+        .clone();
+
+    let name_col = column(&headers, layout.name)
+        .ok_or_else(|| StoreError::Validation(format!("CSV is missing the '{}' column", layout.name)))?;
+    let password_col = column(&headers, layout.password)
+        .ok_or_else(|| StoreError::Validation(format!("CSV is missing the '{}' column", layout.password)))?;
+    let username_col = column(&headers, layout.username);
+    let url_col = column(&headers, layout.url);
+    let notes_col = column(&headers, layout.notes);
+
+    let mut parsed = ParsedImport::default();
+
+    for (index, record) in reader.records().enumerate() {
+        // Строка 1 — заголовок
+        let row = index + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                parsed.errors.push(format!("row {}: {}", row, e));
+                continue;
+            }
+        };
+
+        let name = record.get(name_col).unwrap_or("").trim();
+        if name.is_empty() {
+            parsed.errors.push(format!("row {}: missing {}", row, layout.name));
+            continue;
+        }
+
+        let password = record.get(password_col).unwrap_or("");
+        if password.is_empty() {
+            parsed.errors.push(format!("row {}: missing {}", row, layout.password));
+            continue;
+        }
+
+        let username = username_col
+            .and_then(|col| record.get(col))
+            .unwrap_or("")
+            .trim()
+            .to_string();
+
+        parsed.entries.push(PasswordEntry::new(
+            name.to_string(),
+            username,
+            password.to_string(),
+            optional(url_col.and_then(|col| record.get(col))),
+            optional(notes_col.and_then(|col| record.get(col))),
+        ));
+    }
+
+    Ok(parsed)
+}
+
+#[derive(Deserialize)]
+struct BitwardenExport {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    items: Vec<BitwardenItem>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenItem {
+    #[serde(rename = "type")]
+    item_type: u8,
+    #[serde(default)]
+    name: String,
+    notes: Option<String>,
+    login: Option<BitwardenLogin>,
+    fields: Option<Vec<BitwardenField>>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenLogin {
+    username: Option<String>,
+    password: Option<String>,
+    uris: Option<Vec<BitwardenUri>>,
+    totp: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenUri {
+    uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenField {
+    name: Option<String>,
+    value: Option<String>,
+    // 0 — текст, 1 — скрытое значение
+    #[serde(rename = "type", default)]
+    field_type: u8,
+}
+
+fn bitwarden_type_name(item_type: u8) -> &'static str {
+    match item_type {
+        1 => "login",
+        2 => "secure_note",
+        3 => "card",
+        4 => "identity",
+        _ => "unknown",
+    }
+}
+
+// Импортируются только логины; заметки, карты и личные данные
+// учитываются в item_types и пропускаются
+pub fn parse_bitwarden_json<R: Read>(reader: R) -> Result<ParsedImport, StoreError> {
+    let export: BitwardenExport = serde_json::from_reader(reader)
+        .map_err(|e| schema::redacted_json_error("Failed to parse Bitwarden export", &e))?;
+    if export.encrypted {
+        return Err(StoreError::Validation("Encrypted Bitwarden exports are not supported".to_string()));
+    }
+
+    let mut parsed = ParsedImport::default();
+    for (index, item) in export.items.into_iter().enumerate() {
+        *parsed
+            .item_types
+            .entry(bitwarden_type_name(item.item_type).to_string())
+            .or_default() += 1;
+
+        let Some(login) = item.login.filter(|_| item.item_type == 1) else {
+            continue;
+        };
+
+        let name = item.name.trim();
+        if name.is_empty() {
+            parsed.errors.push(format!("item {}: missing name", index + 1));
+            continue;
+        }
+        let Some(password) = login.password.filter(|p| !p.is_empty()) else {
+            parsed.errors.push(format!("item {} ({}): missing password", index + 1, name));
+            continue;
+        };
+
+        let url = login
+            .uris
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|uri| optional(uri.uri.as_deref()));
+
+        let mut entry = PasswordEntry::new(
+            name.to_string(),
+            login.username.unwrap_or_default().trim().to_string(),
+            password,
+            url,
+            optional(item.notes.as_deref()),
+        );
+
+        match crate::validate_totp_secret(login.totp) {
+            Ok(secret) => entry.totp_secret = secret,
+            Err(e) => parsed.errors.push(format!("item {} ({}): TOTP dropped: {}", index + 1, name, e)),
+        }
+
+        entry.custom_fields = item
+            .fields
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|field| {
+                let label = optional(field.name.as_deref())?;
+                Some(CustomField {
+                    label,
+                    value: field.value.unwrap_or_default(),
+                    secret: field.field_type == 1,
+                })
+            })
+            .collect();
+
+        parsed.entries.push(entry);
+    }
+
+    Ok(parsed)
+}
+
+fn same_account(a: &PasswordEntry, b: &PasswordEntry) -> bool {
+    a.name == b.name && a.username == b.username
+}
+
+fn is_duplicate(a: &PasswordEntry, b: &PasswordEntry) -> bool {
+    same_account(a, b) && crypto::secrets_equal(&a.password, &b.password)
+}
+
+// Форматы импорта переносят не все поля записи: чего в файле нет, остается
+// как было. Защита записи (require_reauth, notes_hidden) импортом только
+// добавляется, иначе перезапись из CSV снимала бы ее без confirm_identity
+fn keep_unimported(entry: &mut PasswordEntry, existing: &PasswordEntry) {
+    if entry.tags.is_empty() {
+        entry.tags = existing.tags.clone();
+    }
+    entry.is_favorite = existing.is_favorite;
+    entry.require_reauth |= existing.require_reauth;
+    entry.notes_hidden |= existing.notes_hidden;
+    if entry.card.is_none() && existing.card.is_some() {
+        entry.kind = existing.kind;
+        entry.card = existing.card.clone();
+    }
+    if entry.totp_secret.is_none() {
+        entry.totp_secret = existing.totp_secret.clone();
+    }
+    if entry.custom_fields.is_empty() {
+        entry.custom_fields = existing.custom_fields.clone();
+    }
+    if entry.policy.is_none() {
+        entry.policy = existing.policy.clone();
+    }
+    if entry.expires_at.is_none() {
+        entry.expires_at = existing.expires_at;
+    }
+}
+
+// Точные копии (name, username, password) пропускаются при любой стратегии,
+// поэтому повторный импорт того же файла ничего не дублирует
+pub fn merge(
+    store: &mut HashMap<String, PasswordEntry>,
+    parsed: ParsedImport,
+    strategy: MergeStrategy,
+    history_size: usize,
+) -> ImportSummary {
+    let mut summary = ImportSummary {
+        skipped: parsed.errors.len(),
+        errors: parsed.errors,
+        item_types: parsed.item_types,
+        ..Default::default()
+    };
+
+    for mut entry in parsed.entries {
+        crate::normalize_url(&mut entry);
+        if store.values().any(|existing| !existing.is_trashed() && is_duplicate(existing, &entry)) {
+            summary.duplicates += 1;
+            continue;
+        }
+
+        let conflict = store
+            .values_mut()
+            .find(|existing| !existing.is_trashed() && same_account(existing, &entry));
+        match (strategy, conflict) {
+            (MergeStrategy::Skip, Some(_)) => summary.kept_existing += 1,
+            (MergeStrategy::Overwrite, Some(existing)) => {
+                keep_unimported(&mut entry, existing);
+                existing.replace_with(entry, history_size);
+                summary.overwritten += 1;
+            }
+            _ => {
+                store.insert(entry.id.clone(), entry);
+                summary.imported += 1;
+            }
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overwrite_keeps_protection_and_fields_missing_from_csv() {
+        let mut existing = PasswordEntry::new(
+            "bank".to_string(),
+            "alice".to_string(),
+            "old-password".to_string(),
+            None,
+            None,
+        );
+        existing.require_reauth = true;
+        existing.notes_hidden = true;
+        existing.totp_secret = Some("JBSWY3DPEHPK3PXP".to_string());
+        existing.custom_fields = vec![CustomField {
+            label: "pin".to_string(),
+            value: "1234".to_string(),
+            secret: true,
+        }];
+        let id = existing.id.clone();
+        let mut store = HashMap::from([(id.clone(), existing)]);
+
+        let csv = "name,username,password\nbank,alice,new-password\n";
+        let parsed = parse_csv(csv.as_bytes(), &GENERIC_LAYOUT).unwrap();
+        let summary = merge(&mut store, parsed, MergeStrategy::Overwrite, 5);
+        assert_eq!(summary.overwritten, 1);
+
+        let entry = &store[&id];
+        assert_eq!(entry.password, "new-password");
+        assert!(entry.require_reauth);
+        assert!(entry.notes_hidden);
+        assert_eq!(entry.totp_secret.as_deref(), Some("JBSWY3DPEHPK3PXP"));
+        assert_eq!(entry.custom_fields.len(), 1);
+        assert_eq!(entry.history.len(), 1);
+    }
+}
@@ -0,0 +1,72 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::PasswordEntry;
+
+// Запись, получившая новый id: ее id был пустым или уже занят другой записью
+#[derive(Debug, Serialize)]
+pub struct ReassignedId {
+    // Пустая строка, если id не было
+    pub old_id: String,
+    pub new_id: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct IntegrityReport {
+    pub checked: usize,
+    pub reassigned: Vec<ReassignedId>,
+    // Записи, лежавшие под ключом, отличным от их id; перенесены под свой id
+    pub rekeyed: Vec<String>,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.reassigned.is_empty() && self.rekeyed.is_empty()
+    }
+
+    pub fn changed_ids(&self) -> Vec<String> {
+        self.reassigned
+            .iter()
+            .map(|r| r.new_id.clone())
+            .chain(self.rekeyed.iter().cloned())
+            .collect()
+    }
+}
+
+// В файле, отредактированном вручную или неудачно объединенном, две записи
+// могут нести один id под разными ключами. Собрать их в карту по id как есть
+// нельзя: одна запись молча потерялась бы. id остается у записи, лежащей под
+// ним же в файле (при равенстве — у первой по ключу), остальные получают новые
+pub fn repair(entries: HashMap<String, PasswordEntry>) -> (HashMap<String, PasswordEntry>, IntegrityReport) {
+    let mut report = IntegrityReport {
+        checked: entries.len(),
+        ..Default::default()
+    };
+
+    let mut ordered: Vec<(String, PasswordEntry)> = entries.into_iter().collect();
+    ordered.sort_by(|(a_key, a), (b_key, b)| {
+        (*a_key != a.id)
+            .cmp(&(*b_key != b.id))
+            .then_with(|| a_key.cmp(b_key))
+    });
+
+    let mut repaired = HashMap::with_capacity(ordered.len());
+    for (key, mut entry) in ordered {
+        if entry.id.is_empty() || repaired.contains_key(&entry.id) {
+            let new_id = Uuid::new_v4().to_string();
+            let old_id = std::mem::replace(&mut entry.id, new_id.clone());
+            if old_id.is_empty() {
+                log::info!("Assigned id {} to an entry without one", new_id);
+            } else {
+                log::warn!("Entry id {} is used more than once, reassigned a copy to {}", old_id, new_id);
+            }
+            report.reassigned.push(ReassignedId { old_id, new_id });
+        } else if key != entry.id {
+            log::warn!("Entry {} was stored under key {}, moved to its own id", entry.id, key);
+            report.rekeyed.push(entry.id.clone());
+        }
+        repaired.insert(entry.id.clone(), entry);
+    }
+    (repaired, report)
+}
@@ -1,47 +1,841 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-use std::collections::HashMap;
-use std::sync::Mutex;
-use tauri::State;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, RunEvent, State};
 use serde::{Serialize, Deserialize};
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
-use std::io::{self, ErrorKind};
-use rand::Rng;
+use std::path::{Path, PathBuf};
+use std::io::{ErrorKind, Write};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use url::Url;
+use uuid::Uuid;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+mod audit;
+mod backup;
+mod bundle;
+mod card;
+mod clipboard;
+mod compress;
+mod config;
+mod crypto;
+mod error;
+mod events;
+mod export;
+mod generator;
+mod headless;
+mod import;
+mod integrity;
+mod merge;
+mod pwned;
+mod query;
+mod schema;
+mod sealed;
+mod strength;
+mod sync;
+mod templates;
+mod throttle;
+mod totp;
+mod vault_lock;
+mod vaults;
+mod watcher;
+
+use audit::{AuditReport, VaultStats};
+use card::{CardDetails, EntryKind};
+use clipboard::ClipboardGuard;
+use crypto::{KdfParams, VaultKey};
+use error::StoreError;
+use events::{PasswordHide, StoreChanged};
+use generator::{GeneratedPassword, GenerationLimits, GenerationSettings, GeneratorOptions, PasswordPolicy};
+use import::{ImportSummary, MergeStrategy};
+use integrity::IntegrityReport;
+use merge::{MergeReport, SyncStrategy, VaultDiff};
+use pwned::PwnedResult;
+use query::{Page, SearchHit, SortField};
+use strength::StrengthReport;
+use templates::{EntryTemplate, TemplateOverrides};
+use sync::{MutexExt, RwLockExt};
+use throttle::UnlockThrottle;
+use totp::TotpResult;
+use vault_lock::VaultLock;
+use sealed::EntryMap;
+use vaults::{ActiveVault, VaultInfo, VaultManager};
+use watcher::ContentHash;
+
+// Подставляется вместо секретов в Debug, чтобы они не попали в логи и отчеты о падениях
+const REDACTED: &str = "<redacted>";
+
+// Дополнительное поле записи; secret подсказывает интерфейсу маскировать значение
+#[derive(Clone, Serialize, Deserialize, Zeroize)]
+struct CustomField {
+    label: String,
+    value: String,
+    #[serde(default)]
+    secret: bool,
+}
+
+impl fmt::Debug for CustomField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value: &dyn fmt::Debug = if self.secret { &REDACTED } else { &self.value };
+        f.debug_struct("CustomField")
+            .field("label", &self.label)
+            .field("value", value)
+            .field("secret", &self.secret)
+            .finish()
+    }
+}
+
+// Значение может быть секретом, поэтому сравнивается в постоянное время
+impl PartialEq for CustomField {
+    fn eq(&self, other: &Self) -> bool {
+        self.label == other.label
+            && self.secret == other.secret
+            && crypto::secrets_equal(&self.value, &other.value)
+    }
+}
+
+// Прежний пароль записи; хранится в том же зашифрованном файле и тоже
+// затирается при drop, в том числе при обрезке истории
+#[derive(Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct PasswordHistoryItem {
+    password: String,
+    changed_at: u64,
+}
+
+impl fmt::Debug for PasswordHistoryItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordHistoryItem")
+            .field("password", &REDACTED)
+            .field("changed_at", &self.changed_at)
+            .finish()
+    }
+}
+
+// Вложение хранится внутри записи в base64, поэтому шифруется вместе
+// с хранилищем и попадает в резервные копии без отдельной обработки
+#[derive(Clone, Serialize, Deserialize, Zeroize)]
+struct Attachment {
+    id: String,
+    name: String,
+    size: u64,
+    created_at: u64,
+    data: String,
+}
+
+impl fmt::Debug for Attachment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Attachment")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("size", &self.size)
+            .field("created_at", &self.created_at)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AttachmentInfo {
+    id: String,
+    name: String,
+    size: u64,
+    created_at: u64,
+}
+
+// Все строки записи затираются при drop, чтобы секреты не оставались в освобожденной памяти
+#[derive(Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
 struct PasswordEntry {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    #[zeroize(skip)]
+    kind: EntryKind,
     name: String,
     username: String,
     password: String,
     url: Option<String>,
     notes: Option<String>,
+    // Адрес в том виде, как его ввел пользователь, если он отличается от url
+    #[serde(default)]
+    url_display: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    custom_fields: Vec<CustomField>,
+    // base32-секрет или otpauth:// URI
+    #[serde(default)]
+    totp_secret: Option<String>,
+    // Время создания и последнего изменения (Unix, секунды)
+    #[serde(default)]
+    created_at: u64,
+    #[serde(default)]
+    updated_at: u64,
+    // Срок действия пароля (Unix, секунды)
+    #[serde(default)]
+    expires_at: Option<u64>,
+    // Время перемещения в корзину (Unix, секунды)
+    #[serde(default)]
+    deleted_at: Option<u64>,
+    // Прежние пароли, новые в начале
+    #[serde(default)]
+    history: Vec<PasswordHistoryItem>,
+    #[serde(default)]
+    is_favorite: bool,
+    // Правила сайта для генерации нового пароля
+    #[serde(default)]
+    policy: Option<PasswordPolicy>,
+    // Заметки не отдаются в списках, только через reveal_notes
+    #[serde(default)]
+    notes_hidden: bool,
+    // Пароль и заметки открываются только после confirm_identity, для общих компьютеров
+    #[serde(default)]
+    require_reauth: bool,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+    // Только у записей типа Card
+    #[serde(default)]
+    card: Option<CardDetails>,
+    // Когда пользователь последний раз копировал или открывал пароль (Unix, секунды)
+    #[serde(default)]
+    last_used_at: Option<u64>,
+    // Зашифрованные секреты записи в режиме ленивой расшифровки (sealed.rs);
+    // пока они запечатаны, поля password, notes и остальные пустые
+    #[serde(skip)]
+    sealed: Option<Vec<u8>>,
+}
+
+// Пароль, заметки и TOTP-секрет маскируются; поля и история маскируют себя сами
+impl fmt::Debug for PasswordEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redact = |value: &Option<String>| value.as_ref().map(|_| REDACTED);
+        f.debug_struct("PasswordEntry")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("username", &self.username)
+            .field("password", &REDACTED)
+            .field("url", &self.url)
+            .field("notes", &redact(&self.notes))
+            .field("tags", &self.tags)
+            .field("custom_fields", &self.custom_fields)
+            .field("totp_secret", &redact(&self.totp_secret))
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("expires_at", &self.expires_at)
+            .field("deleted_at", &self.deleted_at)
+            .field("history", &self.history)
+            .field("attachments", &self.attachments)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PasswordEntry {
+    fn new(
+        name: String,
+        username: String,
+        password: String,
+        url: Option<String>,
+        notes: Option<String>,
+    ) -> Self {
+        let now = now_unix();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            username,
+            password,
+            url,
+            notes,
+            url_display: None,
+            tags: Vec::new(),
+            custom_fields: Vec::new(),
+            totp_secret: None,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+            deleted_at: None,
+            history: Vec::new(),
+            is_favorite: false,
+            policy: None,
+            notes_hidden: false,
+            require_reauth: false,
+            attachments: Vec::new(),
+            kind: EntryKind::Login,
+            card: None,
+            last_used_at: None,
+            sealed: None,
+        }
+    }
+    
+    fn is_trashed(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+    
+    // Копия для отдачи во фронтенд: скрытые заметки не покидают бэкенд
+    fn for_display(&self) -> Self {
+        let mut entry = self.clone();
+        if entry.notes_hidden || entry.require_reauth {
+            // Zeroize для Option затирает строку и оставляет None
+            entry.notes.zeroize();
+        }
+        if entry.require_reauth {
+            // Пустая строка; сам пароль отдает get_password после confirm_identity
+            entry.password.zeroize();
+            entry.card.zeroize();
+            entry.totp_secret.zeroize();
+            // Подпись и признак secret остаются, чтобы интерфейс показал поле скрытым
+            for field in entry.custom_fields.iter_mut().filter(|field| field.secret) {
+                field.value.zeroize();
+            }
+        }
+        // Содержимое вложений отдается только через get_attachment,
+        // прежние пароли — только через get_password_history
+        entry.attachments.zeroize();
+        entry.history.zeroize();
+        entry
+    }
+    
+    // Возвращает в присланную фронтендом запись секреты, которые for_display
+    // не отдал для записи с require_reauth: пустой пароль и отсутствующие карта
+    // и TOTP-секрет значат "не менять", как и пустое значение секретного поля
+    // с той же подписью. Явно очищается TOTP-секрет пустой строкой
+    fn restore_hidden(&mut self, existing: &PasswordEntry) {
+        if !existing.require_reauth {
+            return;
+        }
+        if self.password.is_empty() {
+            self.password = existing.password.clone();
+        }
+        if self.card.is_none() && self.kind == EntryKind::Card {
+            self.card = existing.card.clone();
+        }
+        if self.totp_secret.is_none() {
+            self.totp_secret = existing.totp_secret.clone();
+        }
+        for field in self.custom_fields.iter_mut().filter(|field| field.secret && field.value.is_empty()) {
+            if let Some(old) = existing.custom_fields.iter().find(|old| old.secret && old.label == field.label) {
+                field.value = old.value.clone();
+            }
+        }
+    }
+    
+    // Заменяет содержимое записи, сохраняя id, время создания и историю паролей
+    fn replace_with(&mut self, mut entry: PasswordEntry, history_size: usize) {
+        entry.id = std::mem::take(&mut self.id);
+        entry.created_at = self.created_at;
+        entry.updated_at = now_unix();
+        entry.deleted_at = self.deleted_at;
+        
+        // История и вложения ведутся только на бэкенде, присланные с фронтенда игнорируются
+        entry.history = std::mem::take(&mut self.history);
+        entry.attachments = std::mem::take(&mut self.attachments);
+        entry.last_used_at = self.last_used_at;
+        if !crypto::secrets_equal(&entry.password, &self.password) {
+            entry.history.insert(0, PasswordHistoryItem {
+                password: self.password.clone(),
+                changed_at: entry.updated_at,
+            });
+        }
+        entry.history.truncate(history_size);
+        *self = entry;
+    }
+    
+    // Имена полей, которыми записи различаются, для журнала изменений.
+    // Служебные поля (время, история, вложения) не сравниваются
+    fn changed_fields(&self, other: &PasswordEntry) -> Vec<String> {
+        let fields = [
+            ("name", self.name != other.name),
+            ("username", self.username != other.username),
+            ("password", !crypto::secrets_equal(&self.password, &other.password)),
+            ("url", self.url != other.url),
+            ("notes", self.notes != other.notes),
+            ("tags", self.tags != other.tags),
+            ("custom_fields", self.custom_fields != other.custom_fields),
+            ("totp_secret", !optional_secrets_equal(&self.totp_secret, &other.totp_secret)),
+            ("expires_at", self.expires_at != other.expires_at),
+            ("is_favorite", self.is_favorite != other.is_favorite),
+            ("policy", self.policy != other.policy),
+            ("notes_hidden", self.notes_hidden != other.notes_hidden),
+            ("require_reauth", self.require_reauth != other.require_reauth),
+            ("kind", self.kind != other.kind),
+            ("card", self.card != other.card),
+        ];
+        fields
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+}
+
+fn optional_secrets_equal(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => crypto::secrets_equal(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// Теги хранятся обрезанными и в нижнем регистре, чтобы "Work" и "work" совпадали
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+// Проверяет TOTP-секрет при сохранении, чтобы ошибка всплыла сразу, а не при генерации кода
+fn validate_totp_secret(secret: Option<String>) -> Result<Option<String>, StoreError> {
+    match secret.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()) {
+        Some(secret) => {
+            totp::parse(&secret)?;
+            Ok(Some(secret))
+        }
+        None => Ok(None),
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.trim().is_empty())
+}
+
+// Адрес без схемы ("example.com") считаем https
+fn parse_url(url: &str) -> Result<Url, url::ParseError> {
+    let url = url.trim();
+    match Url::parse(url) {
+        Err(url::ParseError::RelativeUrlWithoutBase) => Url::parse(&format!("https://{}", url)),
+        other => other,
+    }
+}
+
+fn validate_url(url: &str) -> Result<(), StoreError> {
+    match parse_url(url) {
+        Ok(parsed) if parsed.has_host() => Ok(()),
+        _ => Err(StoreError::Validation(format!("url: '{}' is not a valid URL", url.trim()))),
+    }
+}
+
+// Каноническая форма адреса: схема добавлена, хост в нижнем регистре,
+// порт по умолчанию и завершающий слэш убраны.
+// "Google.com" и "https://google.com/" дают одно и то же "https://google.com"
+fn canonical_url(url: &str) -> Option<String> {
+    let mut parsed = parse_url(url).ok().filter(Url::has_host)?;
+    if let Some(host) = parsed.host_str().map(str::to_lowercase) {
+        // Для http/https хост уже приведен парсером, остальные схемы приводим сами
+        parsed.set_host(Some(&host)).ok()?;
+    }
+    
+    let mut canonical = parsed.to_string();
+    if parsed.query().is_none() && parsed.fragment().is_none() {
+        while canonical.ends_with('/') {
+            canonical.pop();
+        }
+    }
+    Some(canonical)
+}
+
+// Заменяет url записи канонической формой. Введенный вариант сохраняется в url_display;
+// пришедший с фронтенда url_display остается, пока он указывает на тот же адрес.
+// Неразбираемые адреса (например, из импорта) остаются как есть
+fn normalize_url(entry: &mut PasswordEntry) {
+    let Some(typed) = non_empty(entry.url.take()).map(|url| url.trim().to_string()) else {
+        entry.url_display = None;
+        return;
+    };
+    let Some(canonical) = canonical_url(&typed) else {
+        entry.url = Some(typed);
+        entry.url_display = None;
+        return;
+    };
+    
+    entry.url_display = if typed != canonical {
+        Some(typed)
+    } else {
+        entry.url_display
+            .take()
+            .filter(|display| canonical_url(display).as_deref() == Some(canonical.as_str()))
+    };
+    entry.url = Some(canonical);
+}
+
+// Регистрируемый домен по списку публичных суффиксов: accounts.google.com -> google.com.
+// Для IP-адресов и хостов вроде localhost возвращается сам хост
+fn registrable_domain(url: &str) -> Option<String> {
+    let parsed = parse_url(url).ok()?;
+    let host = parsed.host_str()?;
+    match parsed.host()? {
+        url::Host::Domain(_) => Some(
+            psl::domain_str(host)
+                .unwrap_or(host)
+                .to_string(),
+        ),
+        _ => Some(host.to_string()),
+    }
+}
+
+// Общая проверка полей перед вставкой или обновлением записи
+fn validate_entry(entry: &mut PasswordEntry) -> Result<(), StoreError> {
+    entry.name = entry.name.trim().to_string();
+    if entry.name.is_empty() {
+        return Err(StoreError::Validation("name: must not be empty".to_string()));
+    }
+    if entry.kind == EntryKind::Login && entry.password.trim().is_empty() {
+        return Err(StoreError::Validation("password: must not be empty".to_string()));
+    }
+    match (&mut entry.card, entry.kind) {
+        (Some(card), EntryKind::Card) => card.normalize()?,
+        (None, EntryKind::Card) => {
+            return Err(StoreError::Validation("card: card entries need card details".to_string()));
+        }
+        (Some(_), _) => {
+            return Err(StoreError::Validation("card: only card entries have card details".to_string()));
+        }
+        (None, _) => {}
+    }
+    
+    if let Some(url) = non_empty(entry.url.clone()) {
+        validate_url(&url)?;
+    }
+    normalize_url(entry);
+    entry.notes = non_empty(entry.notes.take());
+    
+    for field in &mut entry.custom_fields {
+        field.label = field.label.trim().to_string();
+        if field.label.is_empty() {
+            return Err(StoreError::Validation("custom_fields: label must not be empty".to_string()));
+        }
+    }
+    
+    entry.totp_secret = validate_totp_secret(entry.totp_secret.take())?;
+    entry.tags = normalize_tags(std::mem::take(&mut entry.tags));
+    if let Some(policy) = &entry.policy {
+        policy.validate()?;
+    }
+    Ok(())
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+const _: fn() = || {
+    fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+    assert_zeroize_on_drop::<PasswordEntry>();
+    assert_zeroize_on_drop::<PasswordHistoryItem>();
+    assert_zeroize_on_drop::<VaultKey>();
+};
+
+fn normalize_entries(entries: HashMap<String, PasswordEntry>) -> HashMap<String, PasswordEntry> {
+    normalize_checked(entries).0
+}
+
+// Старые файлы хранили записи под именем, без поля id и без временных меток.
+// Пустые и повторяющиеся id заменяются новыми, см. integrity::repair
+fn normalize_checked(entries: HashMap<String, PasswordEntry>) -> (HashMap<String, PasswordEntry>, IntegrityReport) {
+    let now = now_unix();
+    let (mut entries, report) = integrity::repair(entries);
+    for entry in entries.values_mut() {
+        if entry.created_at == 0 {
+            entry.created_at = now;
+        }
+        if entry.updated_at == 0 {
+            entry.updated_at = entry.created_at;
+        }
+    }
+    (entries, report)
+}
+
+// Сжатый и несжатый JSON различаются при чтении по сигнатуре gzip,
+// поэтому файлы в обоих видах открываются при любой настройке
+fn encrypt_entries(
+    passwords: &HashMap<String, PasswordEntry>,
+    key: &VaultKey,
+    compress: bool,
+) -> Result<Vec<u8>, StoreError> {
+    let json = schema::serialize(passwords)?;
+    if compress {
+        crypto::encrypt(key, &compress::compress(&json)?)
+    } else {
+        crypto::encrypt(key, &json)
+    }
+}
+
+// На Unix файлы создаются с правами 0600, а каталог приложения получает 0700.
+// На Windows ACL не меняются: файлы наследуют права профиля пользователя
+fn private_options() -> fs::OpenOptions {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+// mode при открытии действует только на новые файлы, существующие поправляем явно
+#[cfg(unix)]
+fn restrict_permissions(path: &Path, mode: u32) -> Result<(), StoreError> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .map_err(|e| StoreError::Io(format!("Failed to set permissions on {}: {}", path.display(), e)))
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path, _mode: u32) -> Result<(), StoreError> {
+    Ok(())
+}
+
+fn set_aside_corrupt(path: &Path) -> Result<PathBuf, StoreError> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".corrupt.{}", now_unix()));
+    let target = path.with_file_name(name);
+    fs::rename(path, &target)
+        .map_err(|e| StoreError::Io(format!("Failed to set aside corrupt passwords file: {}", e)))?;
+    Ok(target)
+}
+
+// Пишем во временный файл рядом и переименовываем поверх: rename атомарен
+// в пределах одной файловой системы, поэтому сбой не оставит обрезанный файл
+fn write_atomically(path: &Path, data: &[u8]) -> Result<(), StoreError> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    
+    let mut file = private_options().open(&tmp_path)
+        .map_err(|e| StoreError::Io(format!("Failed to create temporary file: {}", e)))?;
+    restrict_permissions(&tmp_path, 0o600)?;
+    file.write_all(data)
+        .map_err(|e| StoreError::Io(format!("Failed to write temporary file: {}", e)))?;
+    file.sync_all()
+        .map_err(|e| StoreError::Io(format!("Failed to sync temporary file: {}", e)))?;
+    drop(file);
+    
+    fs::rename(&tmp_path, path)
+        .map_err(|e| StoreError::Io(format!("Failed to replace passwords file: {}", e)))?;
+    
+    Ok(())
+}
+
+// Для экспорта и резервных копий, которые не нужно заменять атомарно
+fn write_private(path: &Path, data: &[u8]) -> Result<(), StoreError> {
+    let mut file = private_options().open(path)
+        .map_err(|e| StoreError::Io(format!("Failed to create file: {}", e)))?;
+    restrict_permissions(path, 0o600)?;
+    file.write_all(data)
+        .map_err(|e| StoreError::Io(format!("Failed to write file: {}", e)))?;
+    
+    Ok(())
+}
+
+// Перезаписывает файл случайными байтами и удаляет его. На SSD и в файловых
+// системах с копированием при записи контроллер может писать в другие блоки,
+// так что старое содержимое не обязательно исчезает физически; шифрование
+// хранилища остается основной защитой, перезапись — дополнительной
+fn shred_file(path: &Path) -> Result<(), StoreError> {
+    let mut file = match fs::OpenOptions::new().write(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(StoreError::Io(format!("Failed to open file for wiping: {}", e))),
+    };
+    let len = file.metadata()
+        .map_err(|e| StoreError::Io(format!("Failed to read file size: {}", e)))?
+        .len();
+    
+    let mut chunk = vec![0u8; 64 * 1024];
+    let mut written = 0;
+    while written < len {
+        let size = chunk.len().min((len - written) as usize);
+        OsRng.fill_bytes(&mut chunk[..size]);
+        file.write_all(&chunk[..size])
+            .map_err(|e| StoreError::Io(format!("Failed to overwrite file: {}", e)))?;
+        written += size as u64;
+    }
+    file.sync_all()
+        .map_err(|e| StoreError::Io(format!("Failed to sync overwritten file: {}", e)))?;
+    drop(file);
+    
+    fs::remove_file(path)
+        .map_err(|e| StoreError::Io(format!("Failed to remove file: {}", e)))
 }
 
+// Вложения лежат внутри хранилища и шифруются целиком при каждой записи,
+// поэтому их размер ограничен
+const MAX_ATTACHMENT_BYTES: u64 = 1024 * 1024;
+const MAX_ATTACHMENTS_PER_ENTRY_BYTES: u64 = 5 * 1024 * 1024;
+
+const DEFAULT_AUTO_LOCK_SECS: u64 = 300;
+// Сколько действует подтверждение мастер-пароля для записей с require_reauth
+const REAUTH_GRANT_SECS: u64 = 60;
+// Сколько показывается пароль из reveal_password, прежде чем его нужно скрыть
+const AUTO_HIDE_SECS: u64 = 15;
+const DEFAULT_HTTP_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_PASSWORD_HISTORY_SIZE: usize = 5;
+// Минимальная оценка strength::estimate для нового мастер-пароля
+const MIN_MASTER_PASSWORD_SCORE: u8 = 3;
+// Изменения копятся и пишутся на диск не чаще одного раза за этот интервал
+const FLUSH_INTERVAL_MS: u64 = 500;
+// Сколько последних сгенерированных паролей помнит сессия
+const GENERATION_HISTORY_SIZE: usize = 10;
+const GENERATION_ATTEMPTS: usize = 100;
+// Записи в корзине старше этого срока удаляются при разблокировке
+const TRASH_RETENTION_DAYS: u64 = 30;
+
 struct PasswordStore {
-    passwords: Mutex<HashMap<String, PasswordEntry>>,
-    file_path: PathBuf,
+    // Имя в VaultManager
+    name: String,
+    passwords: EntryMap,
+    key: Mutex<Option<VaultKey>>,
+    file_path: RwLock<PathBuf>,
+    last_activity: Mutex<Instant>,
+    auto_lock_after: Mutex<Option<Duration>>,
+    dirty: AtomicBool,
+    // Держится на все время flush: выход дожидается записи, которую уже начал
+    // фоновый поток, а не видит сброшенный им флаг dirty и не выходит раньше
+    flushing: Mutex<()>,
+    // Записи на момент begin_batch. Пока пакет открыт, flush ничего не пишет,
+    // а end_batch сохраняет все разом или возвращает этот снимок
+    batch: Mutex<Option<HashMap<String, PasswordEntry>>>,
+    password_history_size: Mutex<usize>,
+    // Только в памяти, на диск не пишется и очищается при блокировке
+    generation_history: Mutex<VecDeque<Zeroizing<String>>>,
+    // Появляется в setup, до этого события никуда не отправляются
+    app_handle: OnceLock<AppHandle>,
+    // Держится все время работы, чтобы второй экземпляр не открыл то же хранилище
+    vault_lock: Mutex<Option<VaultLock>>,
+    // Куда отложен нечитаемый файл хранилища, найденный при запуске
+    corrupt_file: Mutex<Option<PathBuf>>,
+    unlock_throttle: Mutex<UnlockThrottle>,
+    // До какого момента действует confirm_identity; сбрасывается при блокировке
+    reauth_until: Mutex<Option<Instant>>,
+    // Показанные через reveal_password пароли по токену показа
+    reveals: Mutex<HashMap<String, Reveal>>,
+    // Состояние записи до последнего update_password/delete_password.
+    // Уровень отмены один: каждая такая операция заменяет снимок предыдущей
+    undo_snapshot: Mutex<Option<PasswordEntry>>,
+    // Хеш последнего записанного или прочитанного содержимого файла:
+    // по нему наблюдатель отличает собственные записи от внешних
+    known_content: Mutex<Option<ContentHash>>,
+    vault_watcher: Mutex<Option<notify::RecommendedWatcher>>,
+    // Хранилище только в памяти: ничего не читается и не пишется на диск
+    ephemeral: bool,
+    // Зашифрованное содержимое временного хранилища вместо файла: записи
+    // переживают блокировку и открываются тем же мастер-паролем
+    memory_vault: Mutex<Option<Vec<u8>>>,
+    // Почему вместо постоянного хранилища работает временное (см. degraded)
+    degraded_reason: Option<String>,
+    generation_settings: Mutex<GenerationSettings>,
+    // Настройка compress_vault; действует со следующей записи файла
+    compress: AtomicBool,
 }
 
 impl PasswordStore {
-    fn new(config: &tauri::Config) -> Result<Self, String> {
-        // Создаем директорию для хранения данных
-        let app_dir = dirs::data_local_dir()
-            .unwrap_or_else(|| PathBuf::from("./data"))
-            .join("password-manager");
-        
-        // Создаем директорию, если не существует
-        fs::create_dir_all(&app_dir)
-            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    fn from_parts(file_path: PathBuf, passwords: HashMap<String, PasswordEntry>) -> Self {
+        Self {
+            name: config::DEFAULT_VAULT.to_string(),
+            passwords: EntryMap::new(passwords),
+            key: Mutex::new(None),
+            file_path: RwLock::new(file_path),
+            last_activity: Mutex::new(Instant::now()),
+            auto_lock_after: Mutex::new(Some(Duration::from_secs(DEFAULT_AUTO_LOCK_SECS))),
+            dirty: AtomicBool::new(false),
+            flushing: Mutex::new(()),
+            batch: Mutex::new(None),
+            password_history_size: Mutex::new(DEFAULT_PASSWORD_HISTORY_SIZE),
+            generation_history: Mutex::new(VecDeque::new()),
+            app_handle: OnceLock::new(),
+            vault_lock: Mutex::new(None),
+            corrupt_file: Mutex::new(None),
+            unlock_throttle: Mutex::new(UnlockThrottle::default()),
+            reauth_until: Mutex::new(None),
+            reveals: Mutex::new(HashMap::new()),
+            undo_snapshot: Mutex::new(None),
+            known_content: Mutex::new(None),
+            vault_watcher: Mutex::new(None),
+            ephemeral: false,
+            memory_vault: Mutex::new(None),
+            degraded_reason: None,
+            generation_settings: Mutex::new(GenerationSettings::default()),
+            compress: AtomicBool::new(true),
+        }
+    }
+    
+    // Временное хранилище: запасной вариант, если постоянное не открылось,
+    // и способ проверять логику хранилища без файловой системы.
+    // Блокировка, в том числе автоматическая, шифрует записи в memory_vault,
+    // а не стирает их; теряются они только при выходе
+    fn in_memory() -> Self {
+        Self {
+            ephemeral: true,
+            ..Self::from_parts(PathBuf::new(), HashMap::new())
+        }
+    }
+    
+    // Замена постоянного хранилища, которое не открылось. Путь к файлу пустой,
+    // но до него не доходит: ephemeral отключает все записи на диск.
+    // Интерфейс узнает о режиме из store-degraded и vault_status
+    fn degraded(reason: String) -> Self {
+        Self {
+            degraded_reason: Some(reason),
+            ..Self::in_memory()
+        }
+    }
+    
+    fn ensure_persistent(&self) -> Result<(), StoreError> {
+        if self.ephemeral {
+            return Err(StoreError::Validation("Vault is running in memory-only mode".to_string()));
+        }
+        Ok(())
+    }
+    
+    // Операции, которые сами перезаписывают файл, сломали бы откат пакета
+    fn ensure_no_batch(&self) -> Result<(), StoreError> {
+        if self.batch.lock_recover().is_some() {
+            return Err(StoreError::Validation("Finish the current batch with end_batch first".to_string()));
+        }
+        Ok(())
+    }
+    
+    // Счетчик неудачных попыток лежит в throttle_dir: у основного хранилища
+    // это каталог приложения, а не выбранный пользователем каталог файла.
+    // Права выбранного пользователем каталога не трогаем, защищен сам файл
+    fn open(
+        name: &str,
+        vault_dir: &Path,
+        throttle_dir: &Path,
+        generation: GenerationSettings,
+    ) -> Result<Self, StoreError> {
+        fs::create_dir_all(vault_dir)
+            .map_err(|e| StoreError::Io(format!("Failed to create vault directory: {}", e)))?;
+        restrict_permissions(vault_dir, 0o700)?;
         
-        let file_path = app_dir.join("passwords.json");
+        let lock = VaultLock::acquire(vault_dir)?;
+        let file_path = vault_dir.join(config::VAULT_FILE_NAME);
         
-        let passwords = match fs::read_to_string(&file_path) {
+        let mut corrupt = None;
+        let passwords = match fs::read(&file_path) {
+            Ok(data) if crypto::is_encrypted(&data) => {
+                // Зашифрованный файл, данные загрузятся после unlock
+                HashMap::new()
+            },
             Ok(data) => {
-                // Файл существует, десериализуем данные
-                match serde_json::from_str::<HashMap<String, PasswordEntry>>(&data) {
-                    Ok(map) => map,
+                // Старый открытый формат, зашифруется при первом unlock
+                match schema::load(&data) {
+                    Ok(loaded) => normalize_entries(loaded.entries),
                     Err(e) => {
-                        eprintln!("Failed to parse passwords file: {}", e);
+                        // Поврежденный файл не перезаписываем: откладываем его в сторону,
+                        // и unlock не создаст новое хранилище, пока пользователь не решит
+                        log::error!("Failed to parse passwords file: {}", e);
+                        corrupt = Some(set_aside_corrupt(&file_path)?);
                         HashMap::new()
                     }
                 }
@@ -52,118 +846,3073 @@ impl PasswordStore {
             },
             Err(e) => {
                 // Другая ошибка при чтении файла
-                eprintln!("Error reading passwords file: {}", e);
+                log::error!("Error reading passwords file: {}", e);
                 HashMap::new()
             }
         };
         
-        Ok(Self {
-            passwords: Mutex::new(passwords),
-            file_path,
-        })
+        let store = Self {
+            name: name.to_string(),
+            ..Self::from_parts(file_path, passwords)
+        };
+        *store.unlock_throttle.lock_recover() = UnlockThrottle::load(throttle_dir);
+        *store.generation_settings.lock_recover() = generation;
+        *store.vault_lock.lock_recover() = Some(lock);
+        *store.corrupt_file.lock_recover() = corrupt;
+        Ok(store)
     }
     
-    fn save_to_disk(&self) -> Result<(), String> {
-        let passwords = self.passwords.lock()
-            .map_err(|_| "Failed to lock password store".to_string())?;
-            
-        let json = serde_json::to_string_pretty(&*passwords)
-            .map_err(|e| format!("Failed to serialize passwords: {}", e))?;
-            
-        fs::write(&self.file_path, json)
-            .map_err(|e| format!("Failed to write passwords to disk: {}", e))?;
-            
+    fn file_path(&self) -> PathBuf {
+        self.file_path.read_recover().clone()
+    }
+    
+    fn is_locked(&self) -> Result<bool, StoreError> {
+        let key = self.key.lock_recover();
+        Ok(key.is_none())
+    }
+    
+    // Вызывается в начале каждой команды, заодно отмечает активность пользователя
+    fn ensure_unlocked(&self) -> Result<(), StoreError> {
+        if self.is_locked()? {
+            return Err(StoreError::Locked);
+        }
+        self.touch()
+    }
+    
+    fn touch(&self) -> Result<(), StoreError> {
+        *self.last_activity.lock_recover() = Instant::now();
         Ok(())
     }
-}
-
-#[tauri::command]
-fn add_password(
-    state: State<PasswordStore>,
-    name: String,
-    username: String,
-    password: String,
-    url: Option<String>,
-    notes: Option<String>,
-) -> Result<(), String> {
-    let entry = PasswordEntry {
-        name: name.clone(),
-        username,
-        password,
-        url,
-        notes,
-    };
     
-    let mut store = state.passwords.lock().map_err(|_| "Failed to lock password store".to_string())?;
-    store.insert(name, entry);
+    fn lock(&self) -> Result<(), StoreError> {
+        // Несохраненные изменения пишем до того, как ключ будет стерт
+        self.commit_pending()?;
+        self.clear_secrets()
+    }
     
-    // Сохраняем изменения на диск
-    drop(store); // Освобождаем блокировку перед вызовом save_to_disk
-    state.save_to_disk()?;
+    // Выход из приложения: несохраненное пишется на диск, ключ стирается,
+    // блокировка каталога снимается. Ошибки только логируются, выход не прерывается
+    fn close(&self) {
+        if let Err(e) = self.commit_pending() {
+            log::error!("Failed to flush password store on exit: {}", e);
+        }
+        if let Err(e) = self.clear_secrets() {
+            log::error!("Failed to lock password store on exit: {}", e);
+        }
+        self.vault_lock.lock_recover().take();
+    }
     
-    Ok(())
-}
-
-#[tauri::command]
-fn get_passwords(state: State<PasswordStore>) -> Result<Vec<PasswordEntry>, String> {
-    let store = state.passwords.lock().map_err(|_| "Failed to lock password store".to_string())?;
-    Ok(store.values().cloned().collect())
-}
-
-#[tauri::command]
-fn delete_password(state: State<PasswordStore>, name: String) -> Result<(), String> {
-    let mut store = state.passwords.lock().map_err(|_| "Failed to lock password store".to_string())?;
-    store.remove(&name);
+    // Открытый пакет при блокировке и выходе сохраняется, а не теряется
+    fn commit_pending(&self) -> Result<(), StoreError> {
+        if self.batch.lock_recover().is_some() {
+            return self.end_batch();
+        }
+        self.flush()
+    }
     
-    // Сохраняем изменения на диск
-    drop(store); // Освобождаем блокировку перед вызовом save_to_disk
-    state.save_to_disk()?;
+    fn clear_secrets(&self) -> Result<(), StoreError> {
+        let was_unlocked = self.key.lock_recover().take().is_some();
+        self.passwords.write_recover().clear();
+        self.generation_history.lock_recover().clear();
+        self.undo_snapshot.lock_recover().take();
+        self.reauth_until.lock_recover().take();
+        self.reveals.lock_recover().clear();
+        self.batch.lock_recover().take();
+        if was_unlocked {
+            self.emit(events::STORE_LOCKED, ());
+        }
+        Ok(())
+    }
     
-    Ok(())
-}
-
-#[tauri::command]
-fn generate_password(length: u8) -> String {
-    let charset = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!@#$%^&*()";
-    let length = length.max(8).min(32) as usize;
+    // Уничтожает хранилище целиком: записи в памяти затираются, файл
+    // перезаписывается и удаляется. Блокировка процесса остается, следующий
+    // unlock создаст новое пустое хранилище с новым мастер-паролем
+    fn destroy(&self) -> Result<(), StoreError> {
+        // Блокировка записей не дает flusher'у записать файл заново
+        let mut passwords = self.passwords.write_recover();
+        self.vault_watcher.lock_recover().take();
+        let was_unlocked = self.key.lock_recover().take().is_some();
+        passwords.clear();
+        self.dirty.store(false, Ordering::SeqCst);
+        self.generation_history.lock_recover().clear();
+        self.undo_snapshot.lock_recover().take();
+        self.reauth_until.lock_recover().take();
+        self.reveals.lock_recover().clear();
+        self.known_content.lock_recover().take();
+        
+        let corrupt = self.corrupt_file.lock_recover().take();
+        self.memory_vault.lock_recover().take();
+        if !self.ephemeral {
+            let file_path = self.file_path();
+            let mut tmp_name = file_path.file_name().unwrap_or_default().to_os_string();
+            tmp_name.push(".tmp");
+            shred_file(&file_path)?;
+            shred_file(&file_path.with_file_name(tmp_name))?;
+            if let Some(corrupt) = corrupt {
+                shred_file(&corrupt)?;
+            }
+        }
+        
+        drop(passwords);
+        if was_unlocked {
+            self.emit(events::STORE_LOCKED, ());
+        }
+        self.emit(events::STORE_CHANGED, StoreChanged { ids: Vec::new() });
+        Ok(())
+    }
+    
+    fn lock_if_idle(&self) -> Result<bool, StoreError> {
+        let Some(timeout) = *self.auto_lock_after.lock_recover() else {
+            return Ok(false);
+        };
+        if self.is_locked()? || self.last_activity.lock_recover().elapsed() < timeout {
+            return Ok(false);
+        }
+        
+        self.lock()?;
+        Ok(true)
+    }
     
-    let mut rng = rand::thread_rng();
-    let mut password = String::with_capacity(length);
+    // Неверный пароль увеличивает задержку перед следующей попыткой.
+    // Код восстановления подбирается под тем же счетчиком, что и пароль
+    fn unlock(&self, master_password: &str) -> Result<(), StoreError> {
+        self.unlock_throttled(master_password, false)
+    }
     
-    for _ in 0..length {
-        let idx = rng.gen_range(0..charset.len());
-        password.push(charset.chars().nth(idx).unwrap());
+    fn unlock_with_recovery_key(&self, recovery_key: &str) -> Result<(), StoreError> {
+        self.unlock_throttled(recovery_key, true)
     }
     
-    password
-}
-
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    let context = tauri::generate_context!();
+    // Повторный unlock ничего не перечитывает: иначе файл с диска заменил бы
+    // записи в памяти вместе с еще не сохраненными изменениями и открытым пакетом
+    fn unlock_throttled(&self, secret: &str, recovery: bool) -> Result<(), StoreError> {
+        if !self.is_locked()? {
+            return self.touch();
+        }
+        self.throttled(|| self.unlock_with(secret, recovery))
+    }
+    
+    fn throttled<T>(&self, attempt: impl FnOnce() -> Result<T, StoreError>) -> Result<T, StoreError> {
+        let now = now_unix();
+        let mut throttle = self.unlock_throttle.lock_recover();
+        let remaining = throttle.remaining(now);
+        if remaining > 0 {
+            return Err(StoreError::TooManyAttempts(remaining));
+        }
+        
+        let result = attempt();
+        match &result {
+            Ok(_) => throttle.reset(),
+            Err(StoreError::IncorrectPassword) => throttle.record_failure(now),
+            Err(_) => {}
+        }
+        result
+    }
+    
+    // Повторный ввод мастер-пароля открывает записи с require_reauth на REAUTH_GRANT_SECS.
+    // Ошибки считаются в том же счетчике, что и при unlock
+    fn confirm_identity(&self, master_password: &str) -> Result<u64, StoreError> {
+        self.ensure_unlocked()?;
+        self.throttled(|| self.verify_master_password(master_password))?;
+        *self.reauth_until.lock_recover() = Some(Instant::now() + Duration::from_secs(REAUTH_GRANT_SECS));
+        Ok(REAUTH_GRANT_SECS)
+    }
     
-    // Инициализируем хранилище паролей
-    let password_store = match PasswordStore::new(&context.config()) {
-        Ok(store) => store,
-        Err(e) => {
-            eprintln!("Failed to initialize password store: {}", e);
-            // Используем временное хранилище в памяти, если не удалось создать постоянное
-            PasswordStore {
-                passwords: Mutex::new(HashMap::new()),
-                file_path: PathBuf::new(),
+    fn ensure_reauthorized(&self, entry: &PasswordEntry) -> Result<(), StoreError> {
+        if !entry.require_reauth {
+            return Ok(());
+        }
+        match *self.reauth_until.lock_recover() {
+            Some(until) if Instant::now() < until => Ok(()),
+            _ => Err(StoreError::ReauthRequired),
+        }
+    }
+    
+    // Для экспорта и копий: одно подтверждение нужно, если среди записей есть защищенные
+    fn ensure_reauthorized_all<'a>(&self, entries: impl IntoIterator<Item = &'a PasswordEntry>) -> Result<(), StoreError> {
+        entries.into_iter().try_for_each(|entry| self.ensure_reauthorized(entry))
+    }
+    
+    fn unlock_with(&self, master_password: &str, recovery: bool) -> Result<(), StoreError> {
+        if let Some(path) = self.corrupt_file.lock_recover().as_ref() {
+            return Err(StoreError::Corrupt(path.display().to_string()));
+        }
+        
+        let data = if self.ephemeral {
+            self.memory_vault.lock_recover().clone()
+        } else {
+            match fs::read(self.file_path()) {
+                Ok(data) => Some(data),
+                Err(e) if e.kind() == ErrorKind::NotFound => None,
+                Err(e) => return Err(StoreError::Io(format!("Error reading passwords file: {}", e))),
+            }
+        };
+        
+        match data {
+            Some(data) if crypto::is_encrypted(&data) => {
+                let key = if recovery {
+                    crypto::derive_recovery_key_for(master_password, &data)?
+                } else {
+                    crypto::derive_key_for(master_password, &data)?
+                };
+                let plaintext = compress::decompress(&crypto::decrypt(&key, &data)?)?;
+                
+                let loaded = schema::load(&plaintext)?;
+                let (entries, integrity) = normalize_checked(loaded.entries);
+                
+                *self.passwords.write_recover() = entries;
+                *self.key.lock_recover() = Some(key);
+                *self.known_content.lock_recover() = Some(watcher::content_hash(&data));
+                
+                // Файл старой схемы перезаписывается один раз, уже в текущем формате;
+                // исправленные id тоже сохраняются сразу
+                let purged = self.purge_trash_older_than(TRASH_RETENTION_DAYS)?;
+                if loaded.migrated || purged > 0 || !integrity.is_clean() {
+                    self.save_to_disk()?;
+                }
+            },
+            _ if recovery => {
+                return Err(StoreError::Validation("Vault has no recovery key".to_string()));
+            },
+            _ => {
+                // Хранилища еще нет или оно в открытом виде: задаем мастер-пароль и шифруем
+                let key = crypto::derive_key(master_password, KdfParams::default(), crypto::generate_salt())?;
+                *self.key.lock_recover() = Some(key);
+                self.save_to_disk()?;
             }
         }
-    };
+        
+        self.emit(events::STORE_UNLOCKED, ());
+        self.touch()
+    }
     
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .manage(password_store)
-        .invoke_handler(tauri::generate_handler![
-            add_password,
-            get_passwords,
-            delete_password,
-            generate_password
-        ])
-        .run(context)
-        .expect("error while running tauri application");
+    fn purge_trash_older_than(&self, days: u64) -> Result<usize, StoreError> {
+        let cutoff = now_unix().saturating_sub(days * 24 * 60 * 60);
+        let mut store = self.passwords.write_recover();
+        let before = store.len();
+        store.retain(|_, entry| entry.deleted_at.is_none_or(|deleted_at| deleted_at > cutoff));
+        Ok(before - store.len())
+    }
+    
+    // Повторяет генерацию, пока результат совпадает с одним из последних в истории.
+    // Пока хранилище заблокировано, история не пополняется: ее не должно быть
+    // видно тому, кто сидит за заблокированным приложением
+    fn generate_unique<F>(&self, mut generate: F) -> Result<GeneratedPassword, StoreError>
+    where
+        F: FnMut() -> Result<GeneratedPassword, StoreError>,
+    {
+        if self.is_locked()? {
+            return generate();
+        }
+        let mut history = self.generation_history.lock_recover();
+        for _ in 0..GENERATION_ATTEMPTS {
+            let generated = generate()?;
+            if history.iter().any(|previous| crypto::secrets_equal(previous, &generated.value)) {
+                continue;
+            }
+            
+            if history.len() == GENERATION_HISTORY_SIZE {
+                history.pop_front();
+            }
+            history.push_back(Zeroizing::new(generated.value.clone()));
+            return Ok(generated);
+        }
+        
+        Err(StoreError::GenerationFailed(format!(
+            "no password distinct from recent history after {} tries",
+            GENERATION_ATTEMPTS
+        )))
+    }
+    
+    fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+    
+    // Снимает истекшие показы паролей и сообщает интерфейсу, что их пора скрыть
+    fn expire_reveals(&self) {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        self.reveals.lock_recover().retain(|token, reveal| {
+            if reveal.hide_at > now {
+                return true;
+            }
+            expired.push(PasswordHide {
+                reveal_token: token.clone(),
+                id: reveal.entry_id.clone(),
+            });
+            false
+        });
+        for hide in expired {
+            self.emit(events::PASSWORD_HIDE, hide);
+        }
+    }
+    
+    fn emit<P: Serialize + Clone>(&self, event: &str, payload: P) {
+        if let Some(handle) = self.app_handle.get() {
+            if let Err(e) = handle.emit(event, payload) {
+                log::warn!("Failed to emit {} event: {}", event, e);
+            }
+        }
+    }
+    
+    // Изменения запишет на диск фоновый flusher, окна узнают о них из события
+    fn notify_changed(&self, ids: Vec<String>) {
+        self.mark_dirty();
+        self.emit(events::STORE_CHANGED, StoreChanged { ids });
+    }
+    
+    fn flush(&self) -> Result<(), StoreError> {
+        let _flushing = self.flushing.lock_recover();
+        if self.batch.lock_recover().is_some() {
+            return Ok(());
+        }
+        self.flush_locked()
+    }
+    
+    fn flush_locked(&self) -> Result<(), StoreError> {
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        
+        self.save_to_disk().inspect_err(|_| {
+            // Оставляем флаг, чтобы flusher повторил попытку
+            self.mark_dirty();
+        })
+    }
+    
+    // Накопленные до пакета изменения сохраняются сразу, чтобы снимок
+    // для отката совпадал с файлом на диске
+    fn begin_batch(&self) -> Result<(), StoreError> {
+        self.ensure_unlocked()?;
+        
+        let _flushing = self.flushing.lock_recover();
+        if self.batch.lock_recover().is_some() {
+            return Err(StoreError::Validation("A batch is already in progress".to_string()));
+        }
+        self.flush_locked()?;
+        // Снимок берется как есть, без расшифровки запечатанных записей
+        let snapshot = self.passwords.read_sealed().clone();
+        *self.batch.lock_recover() = Some(snapshot);
+        Ok(())
+    }
+    
+    // Одна запись на весь пакет. Если она не удалась, записи в памяти
+    // возвращаются к состоянию на begin_batch, то есть к файлу на диске
+    fn end_batch(&self) -> Result<(), StoreError> {
+        let _flushing = self.flushing.lock_recover();
+        let snapshot = self
+            .batch
+            .lock_recover()
+            .take()
+            .ok_or_else(|| StoreError::Validation("No batch is in progress".to_string()))?;
+        
+        self.dirty.store(false, Ordering::SeqCst);
+        if let Err(e) = self.save_to_disk() {
+            *self.passwords.write_sealed() = snapshot;
+            self.dirty.store(false, Ordering::SeqCst);
+            self.undo_snapshot.lock_recover().take();
+            self.emit(events::STORE_CHANGED, StoreChanged { ids: Vec::new() });
+            return Err(e);
+        }
+        Ok(())
+    }
+    
+    fn compresses(&self) -> bool {
+        self.compress.load(Ordering::SeqCst)
+    }
+    
+    fn set_compress(&self, enabled: bool) {
+        self.compress.store(enabled, Ordering::SeqCst);
+    }
+    
+    // Текущее содержимое хранилища в том виде, в котором оно пишется на диск
+    fn encrypted_snapshot(&self) -> Result<Vec<u8>, StoreError> {
+        let passwords = self.passwords.read_recover();
+        let key = self.key.lock_recover();
+        encrypt_entries(&passwords, key.as_ref().ok_or(StoreError::Locked)?, self.compresses())
+    }
+    
+    // Заменяет файл хранилища зашифрованным содержимым из копии и блокирует хранилище.
+    // Блокировка записи держится, чтобы flusher не перезаписал восстановленный файл
+    fn restore_vault(&self, vault: &[u8]) -> Result<(), StoreError> {
+        let passwords = self.passwords.write_recover();
+        self.dirty.store(false, Ordering::SeqCst);
+        self.write_vault(&self.file_path(), vault)?;
+        self.corrupt_file.lock_recover().take();
+        drop(passwords);
+        
+        self.clear_secrets()
+    }
+    
+    // Ключ удерживается до конца записи: так фоновая запись не пересечется
+    // со сменой мастер-пароля и не перезапишет файл старым ключом
+    fn save_to_disk(&self) -> Result<(), StoreError> {
+        let passwords = self.passwords.read_recover();
+        let key = self.key.lock_recover();
+        let encrypted = encrypt_entries(&passwords, key.as_ref().ok_or(StoreError::Locked)?, self.compresses())?;
+        self.write_vault(&self.file_path(), &encrypted)
+    }
+    
+    // Все записи файла хранилища идут через этот метод. Хеш запоминается до записи,
+    // чтобы событие наблюдателя не успело прийти раньше
+    fn write_vault(&self, path: &Path, data: &[u8]) -> Result<(), StoreError> {
+        if self.ephemeral {
+            *self.memory_vault.lock_recover() = Some(data.to_vec());
+            return Ok(());
+        }
+        *self.known_content.lock_recover() = Some(watcher::content_hash(data));
+        write_atomically(path, data)
+    }
+    
+    // Перечитывает файл, измененный извне (синхронизацией или другой программой).
+    // Несохраненные локальные изменения важнее: они остаются и при следующей
+    // записи заменят внешнюю версию. Если файл зашифрован другим ключом
+    // (мастер-пароль сменили в другом месте), хранилище блокируется
+    fn reload_from_disk(&self) -> Result<(), StoreError> {
+        let data = match fs::read(self.file_path()) {
+            Ok(data) => data,
+            // Между записью во временный файл и rename файла может не быть
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(StoreError::Io(format!("Error reading passwords file: {}", e))),
+        };
+        
+        let hash = watcher::content_hash(&data);
+        if *self.known_content.lock_recover() == Some(hash) {
+            return Ok(());
+        }
+        
+        let mut passwords = self.passwords.write_recover();
+        let key = self.key.lock_recover();
+        let Some(key_ref) = key.as_ref() else {
+            // Заблокированное хранилище прочитает свежий файл при unlock
+            return Ok(());
+        };
+        if self.dirty.load(Ordering::SeqCst) {
+            log::warn!("Vault file changed on disk, keeping unsaved local changes");
+            return Ok(());
+        }
+        
+        match crypto::decrypt(key_ref, &data) {
+            Ok(plaintext) => {
+                let loaded = schema::load(&compress::decompress(&plaintext)?)?;
+                *passwords = normalize_entries(loaded.entries);
+                *self.known_content.lock_recover() = Some(hash);
+                self.undo_snapshot.lock_recover().take();
+            }
+            Err(StoreError::IncorrectPassword) => {
+                drop(key);
+                drop(passwords);
+                return self.clear_secrets();
+            }
+            Err(e) => return Err(e),
+        }
+        
+        drop(key);
+        drop(passwords);
+        self.emit(events::STORE_CHANGED, StoreChanged { ids: Vec::new() });
+        Ok(())
+    }
+    
+    fn start_watching(&self) -> Result<(), StoreError> {
+        let handle = self.app_handle
+            .get()
+            .cloned()
+            .ok_or_else(|| StoreError::Io("Application is not initialized yet".to_string()))?;
+        let name = self.name.clone();
+        let watcher = watcher::watch(&self.file_path(), move || {
+            let Some(store) = handle.state::<VaultManager>().get(&name) else {
+                return;
+            };
+            if let Err(e) = store.reload_from_disk() {
+                log::error!("Failed to reload changed vault file: {}", e);
+            }
+        })?;
+        *self.vault_watcher.lock_recover() = Some(watcher);
+        Ok(())
+    }
+    
+    fn change_master_password(&self, old_password: &str, new_password: &str) -> Result<(), StoreError> {
+        self.ensure_unlocked()?;
+        
+        let report = strength::estimate(new_password);
+        if report.score < MIN_MASTER_PASSWORD_SCORE {
+            return Err(StoreError::Validation(format!(
+                "New master password is too weak: {}",
+                report.warnings.join("; ")
+            )));
+        }
+        
+        let params = self.key.lock_recover().as_ref().ok_or(StoreError::Locked)?.params();
+        self.rekey(old_password, new_password, params)
+    }
+    
+    // Старый пароль проверяем по файлу на диске, ключ из памяти не раскрываем.
+    // Без файла проверяем по снимку, зашифрованному текущим ключом.
+    // Вместо пароля подходит код восстановления, если он создан: так после
+    // unlock_with_recovery_key можно задать новый мастер-пароль
+    fn verify_master_password(&self, old_password: &str) -> Result<VaultKey, StoreError> {
+        let data = if self.ephemeral {
+            self.encrypted_snapshot()?
+        } else {
+            fs::read(self.file_path())
+                .map_err(|e| StoreError::Io(format!("Error reading passwords file: {}", e)))?
+        };
+        let old_key = match crypto::derive_key_for(old_password, &data) {
+            Err(StoreError::IncorrectPassword) if crypto::read_header(&data)?.has_recovery_key() => {
+                crypto::derive_recovery_key_for(old_password, &data)?
+            }
+            result => result?,
+        };
+        crypto::decrypt(&old_key, &data)?;
+        Ok(old_key)
+    }
+    
+    // Перешифровывает хранилище новым ключом: с новым паролем, новыми параметрами KDF
+    // или тем и другим. Соль всегда новая
+    fn rekey(&self, old_password: &str, new_password: &str, params: KdfParams) -> Result<(), StoreError> {
+        self.ensure_unlocked()?;
+        params.validate()?;
+        
+        let old_key = self.verify_master_password(old_password)?;
+        let new_key = crypto::rewrap(&old_key, new_password, params)?;
+        self.replace_key(new_key)
+    }
+    
+    // Код восстановления показывается один раз и нигде не сохраняется.
+    // Новый код заменяет прежний
+    fn add_recovery_key(&self, master_password: &str) -> Result<Zeroizing<String>, StoreError> {
+        self.ensure_unlocked()?;
+        self.ensure_persistent()?;
+        
+        let old_key = self.verify_master_password(master_password)?;
+        let code = crypto::generate_recovery_code();
+        let new_key = crypto::with_recovery_key(master_password, old_key.params(), &code)?;
+        self.replace_key(new_key)?;
+        Ok(code)
+    }
+    
+    // Перезаписывает файл целиком тем же ключом, но в текущей схеме, с текущим
+    // сжатием и новым nonce. Запись атомарная, как и обычное сохранение:
+    // прерванная перезапись оставляет на диске прежний файл. Возвращает размер файла
+    fn rewrite(&self) -> Result<u64, StoreError> {
+        self.ensure_unlocked()?;
+        self.ensure_persistent()?;
+        self.ensure_no_batch()?;
+        
+        let _flushing = self.flushing.lock_recover();
+        let passwords = self.passwords.read_recover();
+        let key = self.key.lock_recover();
+        let encrypted = encrypt_entries(&passwords, key.as_ref().ok_or(StoreError::Locked)?, self.compresses())?;
+        // Несохраненные изменения попадают в этот же файл
+        self.dirty.store(false, Ordering::SeqCst);
+        self.write_vault(&self.file_path(), &encrypted).inspect_err(|_| self.mark_dirty())?;
+        Ok(encrypted.len() as u64)
+    }
+    
+    fn replace_key(&self, new_key: VaultKey) -> Result<(), StoreError> {
+        self.ensure_no_batch()?;
+        // Файл заменяется атомарно, при ошибке на диске остается прежняя версия
+        let passwords = self.passwords.read_recover();
+        let mut key = self.key.lock_recover();
+        let encrypted = encrypt_entries(&passwords, &new_key, self.compresses())?;
+        self.write_vault(&self.file_path(), &encrypted)?;
+        *key = Some(new_key);
+        self.dirty.store(false, Ordering::SeqCst);
+        
+        Ok(())
+    }
+    
+    // Повторное удаление записи из корзины ничего не меняет: срок хранения
+    // в корзине считается от первого удаления
+    fn move_to_trash(&self, id: &str) -> Result<(), StoreError> {
+        let mut store = self.passwords.write_recover();
+        let entry = store.get_mut(id)
+            .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+        if entry.is_trashed() {
+            return Ok(());
+        }
+        *self.undo_snapshot.lock_recover() = Some(entry.clone());
+        entry.deleted_at = Some(now_unix());
+        
+        // На диск изменения запишет фоновый flusher
+        drop(store);
+        self.notify_changed(vec![id.to_string()]);
+        
+        Ok(())
+    }
+    
+    // CSV открытым текстом, поэтому записи с require_reauth попадают в него
+    // только после confirm_identity
+    fn csv_export(&self) -> Result<Vec<u8>, StoreError> {
+        let store = self.passwords.read_recover();
+        let mut entries: Vec<&PasswordEntry> = store.values().filter(|e| !e.is_trashed()).collect();
+        self.ensure_reauthorized_all(entries.iter().copied())?;
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        export::to_csv(&entries, &import::GENERIC_LAYOUT)
+    }
+}
+
+#[tauri::command]
+fn unlock(state: ActiveVault, master_password: String) -> Result<(), StoreError> {
+    let master_password = Zeroizing::new(master_password);
+    state.unlock(&master_password)
+}
+
+#[tauri::command]
+fn change_master_password(
+    state: ActiveVault,
+    old_password: String,
+    new_password: String,
+) -> Result<(), StoreError> {
+    let old_password = Zeroizing::new(old_password);
+    let new_password = Zeroizing::new(new_password);
+    state.change_master_password(&old_password, &new_password)
+}
+
+// Строка, которую нужно ввести дословно, чтобы уничтожить хранилище
+const DESTROY_CONFIRMATION: &str = "DESTROY MY VAULT";
+
+// В отличие от корзины и empty_trash, удаляет само хранилище без возможности
+// восстановления. Резервные копии и экспорты, сделанные раньше, не трогаются
+#[tauri::command]
+fn destroy_vault(state: ActiveVault, confirm: String) -> Result<(), StoreError> {
+    if confirm != DESTROY_CONFIRMATION {
+        return Err(StoreError::Validation(format!(
+            "Type \"{}\" to confirm destroying the vault",
+            DESTROY_CONFIRMATION
+        )));
+    }
+    state.destroy()
+}
+
+// Возвращает, сколько секунд действует подтверждение
+#[tauri::command]
+fn confirm_identity(state: ActiveVault, master_password: String) -> Result<u64, StoreError> {
+    let master_password = Zeroizing::new(master_password);
+    state.confirm_identity(&master_password)
+}
+
+// Код восстановления расшифровывает хранилище без мастер-пароля.
+// Его нужно записать и хранить офлайн, отдельно от устройства:
+// у кого есть код, у того есть все пароли
+#[tauri::command]
+fn generate_recovery_key(state: ActiveVault, master_password: String) -> Result<String, StoreError> {
+    let master_password = Zeroizing::new(master_password);
+    let code = state.add_recovery_key(&master_password)?;
+    Ok(code.to_string())
+}
+
+// После разблокировки кодом стоит сменить мастер-пароль: change_master_password
+// принимает код восстановления вместо старого пароля
+#[tauri::command]
+fn unlock_with_recovery_key(state: ActiveVault, recovery_key: String) -> Result<(), StoreError> {
+    let recovery_key = Zeroizing::new(recovery_key);
+    state.unlock_with_recovery_key(&recovery_key)
+}
+
+#[tauri::command]
+fn get_kdf_params(state: ActiveVault) -> Result<KdfParams, StoreError> {
+    state.ensure_unlocked()?;
+    let key = state.key.lock_recover();
+    Ok(key.as_ref().ok_or(StoreError::Locked)?.params())
+}
+
+#[derive(Debug, Serialize)]
+struct RewriteReport {
+    size: u64,
+    schema_version: u32,
+}
+
+// Мастер-пароль не нужен: ключ и параметры KDF остаются прежними,
+// для их смены есть set_kdf_params
+#[tauri::command]
+fn rewrite_vault(state: ActiveVault) -> Result<RewriteReport, StoreError> {
+    let size = state.rewrite()?;
+    Ok(RewriteReport {
+        size,
+        schema_version: schema::CURRENT_SCHEMA_VERSION,
+    })
+}
+
+// Без params хранилище перешифровывается с параметрами по умолчанию
+#[tauri::command]
+fn set_kdf_params(
+    state: ActiveVault,
+    master_password: String,
+    params: Option<KdfParams>,
+) -> Result<(), StoreError> {
+    let master_password = Zeroizing::new(master_password);
+    state.rekey(&master_password, &master_password, params.unwrap_or_default())
+}
+
+// Переносит файл хранилища в указанный каталог и запоминает выбор в настройках.
+// Сначала пишется копия на новом месте, старый файл удаляется только после этого
+#[tauri::command]
+fn set_vault_path(state: ActiveVault, path: String) -> Result<String, StoreError> {
+    state.ensure_persistent()?;
+    let dir = PathBuf::from(path);
+    config::ensure_writable(&dir)?;
+    restrict_permissions(&dir, 0o700)?;
+    
+    let target = dir.join(config::VAULT_FILE_NAME);
+    if target == state.file_path() {
+        return Ok(target.to_string_lossy().into_owned());
+    }
+    let new_lock = VaultLock::acquire(&dir)?;
+    
+    // Блокировка записи не дает flusher'у писать в старый файл во время переноса
+    let passwords = state.passwords.write_recover();
+    let mut file_path = state.file_path.write_recover();
+    if target.exists() {
+        return Err(StoreError::Validation("Target directory already contains a vault".to_string()));
+    }
+    
+    let moved = match fs::read(&*file_path) {
+        Ok(data) => {
+            state.write_vault(&target, &data)?;
+            true
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => false,
+        Err(e) => return Err(StoreError::Io(format!("Error reading passwords file: {}", e))),
+    };
+    
+    let app_dir = config::app_data_dir();
+    let mut settings = config::load(&app_dir);
+    config::set_vault_dir(&mut settings, &state.name, dir);
+    if let Err(e) = config::save(&app_dir, &settings) {
+        if moved {
+            let _ = fs::remove_file(&target);
+        }
+        return Err(e);
+    }
+    
+    let old_path = std::mem::replace(&mut *file_path, target.clone());
+    *state.vault_lock.lock_recover() = Some(new_lock);
+    if moved {
+        if let Err(e) = fs::remove_file(&old_path) {
+            log::warn!("Failed to remove old passwords file: {}", e);
+        }
+    }
+    drop(file_path);
+    drop(passwords);
+    
+    // Наблюдатель смотрел в старый каталог
+    if state.vault_watcher.lock_recover().is_some() {
+        state.start_watching()?;
+    }
+    
+    state.touch()?;
+    Ok(target.to_string_lossy().into_owned())
+}
+
+// Наблюдение за файлом включается вручную. Блокировка хранилища не пускает
+// второй экземпляр приложения, так что внешние изменения приходят только
+// от синхронизации или сторонних программ, и об этом пользователь знает сам
+#[tauri::command]
+fn watch_vault(state: ActiveVault, enabled: bool) -> Result<bool, StoreError> {
+    state.ensure_persistent()?;
+    if enabled {
+        state.start_watching()?;
+    } else {
+        state.vault_watcher.lock_recover().take();
+    }
+    Ok(enabled)
+}
+
+// Состояние хранилища для экрана запуска: разблокировка, первая настройка
+// или миграция. Пароль не нужен, секреты не читаются. Для зашифрованного
+// заблокированного файла версия схемы и число записей неизвестны
+#[derive(Debug, Serialize)]
+struct VaultStatus {
+    exists: bool,
+    encrypted: bool,
+    locked: bool,
+    schema_version: Option<u32>,
+    entry_count: Option<usize>,
+    needs_migration: bool,
+    corrupt: bool,
+    in_memory: bool,
+    // Причина, по которой постоянное хранилище не открылось; изменения не сохраняются
+    degraded: Option<String>,
+    recovery_key: bool,
+    lazy_decryption: bool,
+    compressed: bool,
+}
+
+#[tauri::command]
+fn vault_status(state: ActiveVault) -> Result<VaultStatus, StoreError> {
+    let locked = state.is_locked()?;
+    let mut status = VaultStatus {
+        exists: false,
+        encrypted: false,
+        locked,
+        schema_version: None,
+        entry_count: None,
+        needs_migration: false,
+        corrupt: state.corrupt_file.lock_recover().is_some(),
+        in_memory: state.ephemeral,
+        degraded: state.degraded_reason.clone(),
+        recovery_key: false,
+        lazy_decryption: state.passwords.is_lazy(),
+        compressed: state.compresses(),
+    };
+    
+    if state.ephemeral {
+        status.exists = state.memory_vault.lock_recover().is_some();
+        status.encrypted = status.exists;
+    } else {
+        match fs::read(state.file_path()) {
+            Ok(data) => {
+                status.exists = true;
+                status.encrypted = crypto::is_encrypted(&data);
+                status.recovery_key = crypto::read_header(&data).is_ok_and(|header| header.has_recovery_key());
+                if !status.encrypted {
+                    // Открытый файл зашифруется при первом unlock
+                    status.needs_migration = true;
+                    if let Some((version, count)) = schema::inspect(&data) {
+                        status.schema_version = Some(version);
+                        status.entry_count = Some(count);
+                    }
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {},
+            Err(e) => return Err(StoreError::Io(format!("Error reading passwords file: {}", e))),
+        }
+    }
+    
+    if !locked {
+        let store = state.passwords.read_recover();
+        status.schema_version = Some(schema::CURRENT_SCHEMA_VERSION);
+        status.entry_count = Some(store.values().filter(|e| !e.is_trashed()).count());
+    }
+    
+    Ok(status)
+}
+
+#[tauri::command]
+fn get_corrupt_vault_path(state: ActiveVault) -> Result<Option<String>, StoreError> {
+    Ok(state.corrupt_file.lock_recover().as_ref().map(|path| path.display().to_string()))
+}
+
+// Отложенный файл остается на диске, следующий unlock создаст новое хранилище
+#[tauri::command]
+fn dismiss_corrupt_vault(state: ActiveVault) -> Result<(), StoreError> {
+    state.corrupt_file.lock_recover().take();
+    Ok(())
+}
+
+#[tauri::command]
+fn list_vaults(manager: State<VaultManager>) -> Result<Vec<VaultInfo>, StoreError> {
+    manager.list()
+}
+
+#[tauri::command]
+fn create_vault(manager: State<VaultManager>, name: String) -> Result<(), StoreError> {
+    manager.create(&name)
+}
+
+// Остальные хранилища не блокируются: каждое разблокируется своим паролем
+#[tauri::command]
+fn switch_vault(manager: State<VaultManager>, name: String) -> Result<(), StoreError> {
+    manager.switch(&name)
+}
+
+#[tauri::command]
+fn delete_vault(manager: State<VaultManager>, name: String, confirm: bool) -> Result<(), StoreError> {
+    if !confirm {
+        return Err(StoreError::Validation("Deleting a vault removes its file and must be confirmed".to_string()));
+    }
+    manager.delete(&name)
+}
+
+#[tauri::command]
+fn is_locked(state: ActiveVault) -> Result<bool, StoreError> {
+    state.is_locked()
+}
+
+#[tauri::command]
+fn flush(state: ActiveVault) -> Result<(), StoreError> {
+    state.flush()
+}
+
+// Для скриптов и массовых изменений: между begin_batch и end_batch
+// изменения не пишутся на диск, end_batch сохраняет их одной записью
+#[tauri::command]
+fn begin_batch(state: ActiveVault) -> Result<(), StoreError> {
+    state.begin_batch()
+}
+
+#[tauri::command]
+fn end_batch(state: ActiveVault) -> Result<(), StoreError> {
+    state.end_batch()
+}
+
+#[tauri::command]
+fn lock(state: ActiveVault) -> Result<(), StoreError> {
+    state.lock()
+}
+
+// 0 отключает автоблокировку
+#[tauri::command]
+fn auto_lock_after(state: ActiveVault, seconds: u64) -> Result<(), StoreError> {
+    let timeout = (seconds > 0).then(|| Duration::from_secs(seconds));
+    *state.auto_lock_after.lock_recover() = timeout;
+    state.touch()
+}
+
+// 0 отключает историю паролей для последующих изменений
+#[tauri::command]
+fn password_history_size(state: ActiveVault, size: usize) -> Result<(), StoreError> {
+    *state.password_history_size.lock_recover() = size;
+    state.touch()
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn add_password(
+    state: ActiveVault,
+    name: String,
+    username: String,
+    password: String,
+    url: Option<String>,
+    notes: Option<String>,
+    expires_at: Option<u64>,
+    tags: Option<Vec<String>>,
+    totp_secret: Option<String>,
+    custom_fields: Option<Vec<CustomField>>,
+    policy: Option<PasswordPolicy>,
+    kind: Option<EntryKind>,
+    card: Option<CardDetails>,
+) -> Result<String, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let mut entry = PasswordEntry::new(name, username, password, url, notes);
+    entry.kind = kind.unwrap_or_default();
+    entry.card = card;
+    entry.totp_secret = totp_secret;
+    entry.custom_fields = custom_fields.unwrap_or_default();
+    entry.expires_at = expires_at;
+    entry.tags = tags.unwrap_or_default();
+    entry.policy = policy;
+    validate_entry(&mut entry)?;
+    let id = entry.id.clone();
+    
+    let mut store = state.passwords.write_recover();
+    store.insert(id.clone(), entry);
+    
+    // На диск изменения запишет фоновый flusher
+    drop(store);
+    state.notify_changed(vec![id.clone()]);
+    
+    Ok(id)
+}
+
+// Заготовка для похожей записи: новый id, " (copy)" в имени, новые метки
+// времени, без истории паролей и отметки использования. Вложения лежат
+// внутри записи и копируются вместе с ней, но под новыми id
+#[tauri::command]
+fn duplicate_password(state: ActiveVault, id: String) -> Result<PasswordEntry, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let mut store = state.passwords.write_recover();
+    let source = store.get(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    
+    let now = now_unix();
+    let mut copy = source.clone();
+    copy.id = Uuid::new_v4().to_string();
+    copy.name = format!("{} (copy)", source.name);
+    copy.created_at = now;
+    copy.updated_at = now;
+    copy.deleted_at = None;
+    copy.last_used_at = None;
+    copy.history.clear();
+    for attachment in &mut copy.attachments {
+        attachment.id = Uuid::new_v4().to_string();
+        attachment.created_at = now;
+    }
+    validate_entry(&mut copy)?;
+    
+    let new_id = copy.id.clone();
+    let shown = copy.for_display();
+    store.insert(new_id.clone(), copy);
+    drop(store);
+    state.notify_changed(vec![new_id]);
+    
+    Ok(shown)
+}
+
+#[derive(Debug, Serialize)]
+struct GeneratedEntry {
+    id: String,
+    password: GeneratedPassword,
+}
+
+// Создает запись сразу со сгенерированным паролем. Пароль возвращается один раз,
+// и это ровно то значение, что сохранено. Без options — настройки по умолчанию
+#[tauri::command]
+fn add_generated(
+    state: ActiveVault,
+    name: String,
+    username: String,
+    url: Option<String>,
+    notes: Option<String>,
+    length: Option<u8>,
+    options: Option<GeneratorOptions>,
+) -> Result<GeneratedEntry, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let settings = state.generation_settings.lock_recover().clone();
+    let length = length.map_or(settings.length, usize::from);
+    let options = options.unwrap_or_else(|| settings.options());
+    let password = state.generate_unique(|| generator::generate(length, &options))?;
+    
+    let mut entry = PasswordEntry::new(name, username, password.value.clone(), url, notes);
+    validate_entry(&mut entry)?;
+    let id = entry.id.clone();
+    
+    state.passwords.write_recover().insert(id.clone(), entry);
+    state.notify_changed(vec![id.clone()]);
+    
+    Ok(GeneratedEntry { id, password })
+}
+
+// Шаблоны общие для всех хранилищ и управляются без разблокировки
+#[tauri::command]
+fn list_templates() -> BTreeMap<String, EntryTemplate> {
+    config::load(&config::app_data_dir()).templates
+}
+
+// Шаблон с тем же именем заменяется
+#[tauri::command]
+fn save_template(name: String, template: EntryTemplate) -> Result<(), StoreError> {
+    templates::validate_name(&name)?;
+    template.validate()?;
+    
+    let app_dir = config::app_data_dir();
+    let mut settings = config::load(&app_dir);
+    settings.templates.insert(name.trim().to_string(), template);
+    config::save(&app_dir, &settings)
+}
+
+#[tauri::command]
+fn delete_template(name: String) -> Result<(), StoreError> {
+    let app_dir = config::app_data_dir();
+    let mut settings = config::load(&app_dir);
+    if settings.templates.remove(name.trim()).is_none() {
+        return Err(StoreError::NotFound(format!("no template named {}", name)));
+    }
+    config::save(&app_dir, &settings)
+}
+
+#[derive(Debug, Serialize)]
+struct TemplateEntry {
+    id: String,
+    // Только если пароль сгенерирован, а не передан в overrides
+    password: Option<GeneratedPassword>,
+}
+
+#[tauri::command]
+fn create_from_template(
+    state: ActiveVault,
+    name: String,
+    overrides: TemplateOverrides,
+) -> Result<TemplateEntry, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let template = config::load(&config::app_data_dir())
+        .templates
+        .remove(name.trim())
+        .ok_or_else(|| StoreError::NotFound(format!("no template named {}", name)))?;
+    
+    let username = overrides.username.or_else(|| template.username.clone()).unwrap_or_default();
+    let url = overrides.url.or_else(|| template.url_for(&overrides.name, &username));
+    let notes = overrides.notes.or_else(|| template.notes.clone());
+    
+    let generated = match overrides.password {
+        Some(_) => None,
+        None => {
+            let settings = template
+                .generation
+                .clone()
+                .unwrap_or_else(|| state.generation_settings.lock_recover().clone());
+            let options = settings.options();
+            Some(state.generate_unique(|| generator::generate(settings.length, &options))?)
+        }
+    };
+    let password = match (&generated, overrides.password) {
+        (Some(generated), _) => generated.value.clone(),
+        (None, password) => password.unwrap_or_default(),
+    };
+    
+    let mut entry = PasswordEntry::new(overrides.name, username, password, url, notes);
+    entry.tags = template.tags.iter().cloned().chain(overrides.tags).collect();
+    validate_entry(&mut entry)?;
+    let id = entry.id.clone();
+    
+    state.passwords.write_recover().insert(id.clone(), entry);
+    state.notify_changed(vec![id.clone()]);
+    
+    Ok(TemplateEntry { id, password: generated })
+}
+
+// Возвращает имена измененных полей
+#[tauri::command]
+fn update_password(state: ActiveVault, id: String, mut entry: PasswordEntry) -> Result<Vec<String>, StoreError> {
+    state.ensure_unlocked()?;
+    // Скрытые заметки фронтенд не видел: отсутствие поля значит "не менять",
+    // а пустая строка — явная очистка
+    let keep_notes = entry.notes.is_none();
+    let history_size = *state.password_history_size.lock_recover();
+    
+    let mut store = state.passwords.write_recover();
+    let existing = store.get_mut(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    
+    entry.restore_hidden(existing);
+    validate_entry(&mut entry)?;
+    if existing.require_reauth && !entry.require_reauth {
+        state.ensure_reauthorized(existing)?;
+    }
+    
+    let snapshot = existing.clone();
+    
+    if keep_notes && (existing.notes_hidden || existing.require_reauth) {
+        entry.notes = existing.notes.take();
+    }
+    
+    // Ключ записи не меняется, переименование затрагивает только name
+    existing.replace_with(entry, history_size);
+    let changed = snapshot.changed_fields(existing);
+    *state.undo_snapshot.lock_recover() = Some(snapshot);
+    
+    drop(store);
+    state.notify_changed(vec![id]);
+    
+    Ok(changed)
+}
+
+// Изменения для patch_password: применяются только переданные поля.
+// Пустая строка очищает url, notes и totp_secret, expires_at = 0 снимает срок
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct EntryPatch {
+    name: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    url: Option<String>,
+    notes: Option<String>,
+    tags: Option<Vec<String>>,
+    custom_fields: Option<Vec<CustomField>>,
+    totp_secret: Option<String>,
+    expires_at: Option<u64>,
+    is_favorite: Option<bool>,
+    policy: Option<PasswordPolicy>,
+    notes_hidden: Option<bool>,
+    require_reauth: Option<bool>,
+    kind: Option<EntryKind>,
+    card: Option<CardDetails>,
+}
+
+impl EntryPatch {
+    fn apply(self, entry: &mut PasswordEntry) {
+        if let Some(name) = self.name {
+            entry.name = name;
+        }
+        if let Some(username) = self.username {
+            entry.username = username;
+        }
+        if let Some(password) = self.password {
+            entry.password = password;
+        }
+        if let Some(url) = self.url {
+            entry.url = Some(url);
+        }
+        if let Some(notes) = self.notes {
+            entry.notes = Some(notes);
+        }
+        if let Some(tags) = self.tags {
+            entry.tags = tags;
+        }
+        if let Some(custom_fields) = self.custom_fields {
+            entry.custom_fields = custom_fields;
+        }
+        if let Some(totp_secret) = self.totp_secret {
+            entry.totp_secret = Some(totp_secret);
+        }
+        if let Some(expires_at) = self.expires_at {
+            entry.expires_at = Some(expires_at).filter(|at| *at > 0);
+        }
+        if let Some(is_favorite) = self.is_favorite {
+            entry.is_favorite = is_favorite;
+        }
+        if let Some(policy) = self.policy {
+            entry.policy = Some(policy);
+        }
+        if let Some(notes_hidden) = self.notes_hidden {
+            entry.notes_hidden = notes_hidden;
+        }
+        if let Some(require_reauth) = self.require_reauth {
+            entry.require_reauth = require_reauth;
+        }
+        if let Some(kind) = self.kind {
+            entry.kind = kind;
+            if kind != EntryKind::Card {
+                entry.card.zeroize();
+            }
+        }
+        if let Some(card) = self.card {
+            entry.card = Some(card);
+        }
+    }
+}
+
+// Меняет только переданные поля, поэтому не затирает то, что окно не загружало.
+// Если ничего не изменилось, запись не трогается: updated_at и история остаются прежними.
+// Возвращает имена измененных полей
+#[tauri::command]
+fn patch_password(state: ActiveVault, id: String, changes: EntryPatch) -> Result<Vec<String>, StoreError> {
+    state.ensure_unlocked()?;
+    let history_size = *state.password_history_size.lock_recover();
+    
+    let mut store = state.passwords.write_recover();
+    let existing = store.get_mut(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    
+    let mut patched = existing.clone();
+    changes.apply(&mut patched);
+    validate_entry(&mut patched)?;
+    
+    let changed = existing.changed_fields(&patched);
+    if changed.is_empty() {
+        return Ok(changed);
+    }
+    if existing.require_reauth && !patched.require_reauth {
+        state.ensure_reauthorized(existing)?;
+    }
+    
+    *state.undo_snapshot.lock_recover() = Some(existing.clone());
+    existing.replace_with(patched, history_size);
+    
+    drop(store);
+    state.notify_changed(vec![id]);
+    
+    Ok(changed)
+}
+
+// Все параметры необязательны: без них возвращаются все записи по имени.
+// kind оставляет записи одного типа
+#[tauri::command]
+fn get_passwords(
+    state: ActiveVault,
+    sort_by: Option<SortField>,
+    ascending: Option<bool>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    favorites_first: Option<bool>,
+    kind: Option<EntryKind>,
+) -> Result<Page<PasswordEntry>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let mut entries: Vec<PasswordEntry> = store
+        .values()
+        .filter(|e| !e.is_trashed() && kind.is_none_or(|kind| e.kind == kind))
+        .map(PasswordEntry::for_display)
+        .collect();
+    drop(store);
+    
+    query::sort(
+        &mut entries,
+        sort_by.unwrap_or_default(),
+        ascending.unwrap_or(true),
+        favorites_first.unwrap_or(false),
+    );
+    Ok(query::paginate(entries, offset.unwrap_or(0), limit))
+}
+
+// Запись без секретов для списков: пароль, заметки, TOTP и поля не покидают бэкенд
+#[derive(Debug, Serialize)]
+struct EntryMeta {
+    id: String,
+    kind: EntryKind,
+    name: String,
+    username: String,
+    url: Option<String>,
+    tags: Vec<String>,
+    updated_at: u64,
+}
+
+#[tauri::command]
+fn get_entries_metadata(state: ActiveVault) -> Result<Vec<EntryMeta>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    // Метаданные не запечатываются, расшифровка не нужна
+    let store = state.passwords.read_sealed();
+    let mut entries: Vec<EntryMeta> = store
+        .values()
+        .filter(|e| !e.is_trashed())
+        .map(|e| EntryMeta {
+            id: e.id.clone(),
+            kind: e.kind,
+            name: e.name.clone(),
+            username: e.username.clone(),
+            url: e.url.clone(),
+            tags: e.tags.clone(),
+            updated_at: e.updated_at,
+        })
+        .collect();
+    drop(store);
+    
+    entries.sort_by(|a, b| {
+        a.name.to_lowercase()
+            .cmp(&b.name.to_lowercase())
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    Ok(entries)
+}
+
+// Вызывается интерфейсом, когда пользователь копирует или открывает пароль.
+// Фоновые операции (аудит, экспорт) использованием не считаются.
+// Содержимое записи не меняется, поэтому событие store-changed не отправляется
+#[tauri::command]
+fn mark_used(state: ActiveVault, id: String) -> Result<(), StoreError> {
+    state.ensure_unlocked()?;
+    
+    let mut store = state.passwords.write_sealed();
+    let entry = store.get_mut(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    entry.last_used_at = Some(now_unix());
+    
+    drop(store);
+    state.mark_dirty();
+    
+    Ok(())
+}
+
+#[tauri::command]
+fn get_recently_used(state: ActiveVault, limit: Option<usize>) -> Result<Vec<PasswordEntry>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let mut entries: Vec<&PasswordEntry> = store
+        .values()
+        .filter(|e| !e.is_trashed() && e.last_used_at.is_some())
+        .collect();
+    entries.sort_by(|a, b| b.last_used_at.cmp(&a.last_used_at).then_with(|| a.id.cmp(&b.id)));
+    if let Some(limit) = limit {
+        entries.truncate(limit);
+    }
+    
+    Ok(entries.into_iter().map(PasswordEntry::for_display).collect())
+}
+
+#[tauri::command]
+fn get_favorites(state: ActiveVault) -> Result<Vec<PasswordEntry>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    Ok(store.values()
+        .filter(|e| e.is_favorite && !e.is_trashed())
+        .map(PasswordEntry::for_display)
+        .collect())
+}
+
+// Возвращает новое состояние отметки
+#[tauri::command]
+fn toggle_favorite(state: ActiveVault, id: String) -> Result<bool, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let mut store = state.passwords.write_sealed();
+    let entry = store.get_mut(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    entry.is_favorite = !entry.is_favorite;
+    let is_favorite = entry.is_favorite;
+    
+    drop(store);
+    state.notify_changed(vec![id]);
+    
+    Ok(is_favorite)
+}
+
+#[tauri::command]
+fn get_password(state: ActiveVault, id: String) -> Result<PasswordEntry, StoreError> {
+    state.ensure_unlocked()?;
+    
+    // В режиме ленивой расшифровки расшифровывается только эта запись
+    let store = state.passwords.read_sealed();
+    let entry = store.get(&id)
+        .map(|entry| state.passwords.unsealed(entry))
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    drop(store);
+    state.ensure_reauthorized(&entry)?;
+    
+    let mut shown = entry.for_display();
+    if entry.require_reauth {
+        shown.password = entry.password.clone();
+        shown.card = entry.card.clone();
+        shown.totp_secret = entry.totp_secret.clone();
+        shown.custom_fields = entry.custom_fields.clone();
+        if !entry.notes_hidden {
+            shown.notes = entry.notes.clone();
+        }
+    }
+    Ok(shown)
+}
+
+struct Reveal {
+    entry_id: String,
+    hide_at: Instant,
+}
+
+#[derive(Serialize, Zeroize, ZeroizeOnDrop)]
+struct RevealedPassword {
+    value: String,
+    reveal_token: String,
+    auto_hide_secs: u64,
+}
+
+// Только пароль, с ограниченным временем показа. Когда auto_hide_secs
+// истекут, бэкенд сам отправит password-hide с этим токеном, так что пароль
+// скрывается, даже если таймер интерфейса не сработал
+#[tauri::command]
+fn reveal_password(state: ActiveVault, id: String) -> Result<RevealedPassword, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_sealed();
+    let entry = store.get(&id)
+        .map(|entry| state.passwords.unsealed(entry))
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    drop(store);
+    state.ensure_reauthorized(&entry)?;
+    
+    let reveal_token = Uuid::new_v4().to_string();
+    state.reveals.lock_recover().insert(reveal_token.clone(), Reveal {
+        entry_id: id,
+        hide_at: Instant::now() + Duration::from_secs(AUTO_HIDE_SECS),
+    });
+    Ok(RevealedPassword {
+        value: entry.password.clone(),
+        reveal_token,
+        auto_hide_secs: AUTO_HIDE_SECS,
+    })
+}
+
+// Интерфейс подтверждает, что скрыл пароль. false — показ уже истек
+// (или хранилище блокировалось) и password-hide отправлен раньше
+#[tauri::command]
+fn mark_hidden(state: ActiveVault, reveal_token: String) -> bool {
+    state.reveals.lock_recover().remove(&reveal_token).is_some()
+}
+
+#[derive(Serialize)]
+struct EntryBatch {
+    entries: Vec<PasswordEntry>,
+    missing: Vec<String>,
+}
+
+// Записи возвращаются в порядке ids, повторы пропускаются
+#[tauri::command]
+fn get_passwords_by_ids(state: ActiveVault, ids: Vec<String>) -> Result<EntryBatch, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let mut seen = HashSet::new();
+    let mut batch = EntryBatch { entries: Vec::new(), missing: Vec::new() };
+    for id in ids {
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        match store.get(&id) {
+            Some(entry) => batch.entries.push(entry.for_display()),
+            None => batch.missing.push(id),
+        }
+    }
+    Ok(batch)
+}
+
+#[tauri::command]
+fn reveal_notes(state: ActiveVault, id: String) -> Result<Option<String>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let entry = store.get(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    state.ensure_reauthorized(entry)?;
+    Ok(entry.notes.clone())
+}
+
+#[tauri::command]
+fn get_password_history(state: ActiveVault, id: String) -> Result<Vec<PasswordHistoryItem>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let entry = store.get(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    state.ensure_reauthorized(entry)?;
+    Ok(entry.history.clone())
+}
+
+#[tauri::command]
+fn get_entry_domain(state: ActiveVault, id: String) -> Result<Option<String>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let entry = store.get(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    Ok(entry.url.as_deref().and_then(registrable_domain))
+}
+
+#[tauri::command]
+fn attach_file(state: ActiveVault, id: String, path: String) -> Result<AttachmentInfo, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let path = PathBuf::from(path);
+    let size = fs::metadata(&path)
+        .map_err(|e| StoreError::Io(format!("Failed to read attachment: {}", e)))?
+        .len();
+    if size > MAX_ATTACHMENT_BYTES {
+        return Err(StoreError::Validation(format!(
+            "Attachment is larger than {} bytes",
+            MAX_ATTACHMENT_BYTES
+        )));
+    }
+    let data = Zeroizing::new(
+        fs::read(&path).map_err(|e| StoreError::Io(format!("Failed to read attachment: {}", e)))?,
+    );
+    let name = path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "attachment".to_string());
+    
+    let mut store = state.passwords.write_recover();
+    let entry = store.get_mut(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    let total: u64 = entry.attachments.iter().map(|a| a.size).sum();
+    if total + data.len() as u64 > MAX_ATTACHMENTS_PER_ENTRY_BYTES {
+        return Err(StoreError::Validation(format!(
+            "Attachments of an entry may not exceed {} bytes",
+            MAX_ATTACHMENTS_PER_ENTRY_BYTES
+        )));
+    }
+    
+    let attachment = Attachment {
+        id: Uuid::new_v4().to_string(),
+        name,
+        size: data.len() as u64,
+        created_at: now_unix(),
+        data: data_encoding::BASE64.encode(&data),
+    };
+    let info = AttachmentInfo {
+        id: attachment.id.clone(),
+        name: attachment.name.clone(),
+        size: attachment.size,
+        created_at: attachment.created_at,
+    };
+    entry.attachments.push(attachment);
+    
+    drop(store);
+    state.notify_changed(vec![id]);
+    
+    Ok(info)
+}
+
+#[tauri::command]
+fn list_attachments(state: ActiveVault, id: String) -> Result<Vec<AttachmentInfo>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let entry = store.get(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    Ok(entry.attachments
+        .iter()
+        .map(|a| AttachmentInfo {
+            id: a.id.clone(),
+            name: a.name.clone(),
+            size: a.size,
+            created_at: a.created_at,
+        })
+        .collect())
+}
+
+#[tauri::command]
+fn get_attachment(state: ActiveVault, id: String, attachment_id: String) -> Result<Vec<u8>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let entry = store.get(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    state.ensure_reauthorized(entry)?;
+    let attachment = entry.attachments.iter()
+        .find(|a| a.id == attachment_id)
+        .ok_or_else(|| StoreError::NotFound(format!("no attachment with id {}", attachment_id)))?;
+    data_encoding::BASE64
+        .decode(attachment.data.as_bytes())
+        .map_err(|_| StoreError::Serialize("Attachment data is corrupted".to_string()))
+}
+
+#[tauri::command]
+fn remove_attachment(state: ActiveVault, id: String, attachment_id: String) -> Result<(), StoreError> {
+    state.ensure_unlocked()?;
+    
+    let mut store = state.passwords.write_recover();
+    let entry = store.get_mut(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    let index = entry.attachments.iter()
+        .position(|a| a.id == attachment_id)
+        .ok_or_else(|| StoreError::NotFound(format!("no attachment with id {}", attachment_id)))?;
+    entry.attachments.remove(index).zeroize();
+    
+    drop(store);
+    state.notify_changed(vec![id]);
+    
+    Ok(())
+}
+
+// Пароль и заметки намеренно не участвуют в поиске, чтобы секреты
+// не сопоставлялись с тем, что пользователь ввел в строку поиска
+#[tauri::command]
+fn search_passwords(state: ActiveVault, query: String, exact: Option<bool>) -> Result<Vec<SearchHit>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let query = query::fold(query.trim());
+    let entries = store.values().filter(|e| !e.is_trashed());
+    
+    // exact — прежний поиск по подстроке, без опечаток и подпоследовательностей
+    let mut hits: Vec<SearchHit> = if query.is_empty() {
+        entries
+            .map(|e| SearchHit { entry: e.for_display(), score: 0 })
+            .collect()
+    } else if exact.unwrap_or(false) {
+        entries
+            .filter(|p| {
+                query::fold(&p.name).contains(&query)
+                    || query::fold(&p.username).contains(&query)
+                    || p.url.as_ref().is_some_and(|url| query::fold(url).contains(&query))
+            })
+            .map(|e| SearchHit { entry: e.for_display(), score: 0 })
+            .collect()
+    } else {
+        entries
+            .filter_map(|e| query::score(e, &query).map(|score| SearchHit { entry: e.for_display(), score }))
+            .collect()
+    };
+    query::rank(&mut hits);
+    Ok(hits)
+}
+
+#[tauri::command]
+fn get_passwords_by_tag(state: ActiveVault, tag: String) -> Result<Vec<PasswordEntry>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let tag = tag.trim().to_lowercase();
+    let store = state.passwords.read_recover();
+    Ok(store
+        .values()
+        .filter(|e| !e.is_trashed() && e.tags.contains(&tag))
+        .map(PasswordEntry::for_display)
+        .collect())
+}
+
+// Счетчики не копируют записи и не передают секреты через IPC
+#[tauri::command]
+fn count_passwords(state: ActiveVault) -> Result<usize, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    Ok(store.values().filter(|e| !e.is_trashed()).count())
+}
+
+#[tauri::command]
+fn count_by_tag(state: ActiveVault, tag: String) -> Result<usize, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let tag = tag.trim().to_lowercase();
+    let store = state.passwords.read_recover();
+    Ok(store
+        .values()
+        .filter(|e| !e.is_trashed() && e.tags.contains(&tag))
+        .count())
+}
+
+#[tauri::command]
+fn list_all_tags(state: ActiveVault) -> Result<Vec<String>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let mut tags: Vec<String> = store
+        .values()
+        .filter(|e| !e.is_trashed())
+        .flat_map(|e| e.tags.iter().cloned())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    
+    Ok(tags)
+}
+
+#[derive(Debug, Serialize)]
+struct TagCount {
+    // None — записи без тегов; такой элемент идет последним
+    tag: Option<String>,
+    count: usize,
+}
+
+// Теги в записях уже нормализованы (normalize_tags), так что одинаковые
+// теги в разном регистре не считаются по отдельности
+#[tauri::command]
+fn tag_summary(state: ActiveVault) -> Result<Vec<TagCount>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    let mut untagged = 0;
+    for entry in store.values().filter(|e| !e.is_trashed()) {
+        if entry.tags.is_empty() {
+            untagged += 1;
+        }
+        for tag in &entry.tags {
+            *counts.entry(tag.as_str()).or_default() += 1;
+        }
+    }
+    
+    let mut summary: Vec<TagCount> = counts
+        .into_iter()
+        .map(|(tag, count)| TagCount { tag: Some(tag.to_string()), count })
+        .collect();
+    summary.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    if untagged > 0 {
+        summary.push(TagCount { tag: None, count: untagged });
+    }
+    Ok(summary)
+}
+
+fn parse_tag(tag: &str) -> Result<String, StoreError> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() {
+        return Err(StoreError::Validation("tag: must not be empty".to_string()));
+    }
+    Ok(tag)
+}
+
+// Общая часть массовых операций с тегами: правит записи под одной блокировкой,
+// приводит их теги к норме и один раз сообщает об изменении.
+// edit возвращает true, если запись изменилась
+fn edit_tags(
+    state: &PasswordStore,
+    ids: Option<&[String]>,
+    mut edit: impl FnMut(&mut Vec<String>) -> bool,
+) -> usize {
+    let mut changed = Vec::new();
+    let mut store = state.passwords.write_recover();
+    for entry in store.values_mut() {
+        if ids.is_some_and(|ids| !ids.contains(&entry.id)) {
+            continue;
+        }
+        if edit(&mut entry.tags) {
+            entry.tags = normalize_tags(std::mem::take(&mut entry.tags));
+            changed.push(entry.id.clone());
+        }
+    }
+    
+    drop(store);
+    if !changed.is_empty() {
+        state.notify_changed(changed.clone());
+    }
+    changed.len()
+}
+
+#[tauri::command]
+fn add_tag_to(state: ActiveVault, ids: Vec<String>, tag: String) -> Result<usize, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let tag = parse_tag(&tag)?;
+    Ok(edit_tags(&state, Some(&ids), |tags| {
+        if tags.contains(&tag) {
+            return false;
+        }
+        tags.push(tag.clone());
+        true
+    }))
+}
+
+#[tauri::command]
+fn remove_tag_from(state: ActiveVault, ids: Vec<String>, tag: String) -> Result<usize, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let tag = parse_tag(&tag)?;
+    Ok(edit_tags(&state, Some(&ids), |tags| {
+        let before = tags.len();
+        tags.retain(|t| *t != tag);
+        tags.len() != before
+    }))
+}
+
+// Переименовывает тег во всех записях, включая корзину. Если новый тег
+// у записи уже есть, они сливаются в один
+#[tauri::command]
+fn rename_tag(state: ActiveVault, old: String, new: String) -> Result<usize, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let old = parse_tag(&old)?;
+    let new = parse_tag(&new)?;
+    if old == new {
+        return Ok(0);
+    }
+    Ok(edit_tags(&state, None, |tags| {
+        let Some(tag) = tags.iter_mut().find(|t| **t == old) else {
+            return false;
+        };
+        *tag = new.clone();
+        true
+    }))
+}
+
+// Просроченные и истекающие в ближайшие within_days дней записи, ближайшие первыми.
+// Метки хранятся как Unix-время, поэтому сравнение всегда идет в UTC
+#[tauri::command]
+fn get_expiring_passwords(state: ActiveVault, within_days: u64) -> Result<Vec<PasswordEntry>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let deadline = now_unix().saturating_add(within_days.saturating_mul(24 * 60 * 60));
+    let store = state.passwords.read_recover();
+    let mut expiring: Vec<PasswordEntry> = store
+        .values()
+        .filter(|e| !e.is_trashed() && e.expires_at.is_some_and(|expires_at| expires_at <= deadline))
+        .map(PasswordEntry::for_display)
+        .collect();
+    expiring.sort_by_key(|e| e.expires_at);
+    
+    Ok(expiring)
+}
+
+#[tauri::command]
+fn get_totp_code(state: ActiveVault, id: String) -> Result<TotpResult, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let entry = store.get(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    state.ensure_reauthorized(entry)?;
+    let secret = entry.totp_secret.as_deref()
+        .ok_or_else(|| StoreError::NotFound(format!("entry {} has no TOTP secret", id)))?;
+    
+    let params = totp::parse(secret)?;
+    Ok(totp::generate(&params, now_unix()))
+}
+
+// Сетевые команды асинхронные: запрос выполняется в пуле блокирующих задач
+// и не занимает поток, обрабатывающий остальные команды
+async fn run_blocking<T, F>(task: F) -> Result<T, StoreError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, StoreError> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(task)
+        .await
+        .map_err(|e| StoreError::Io(format!("Background task failed: {}", e)))?
+}
+
+// QR-код для переноса TOTP в приложение на телефоне. Содержит секрет,
+// поэтому доступен только при разблокированном хранилище
+#[tauri::command]
+fn totp_qr(state: ActiveVault, id: String) -> Result<String, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let entry = store.get(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    state.ensure_reauthorized(entry)?;
+    let secret = entry.totp_secret.as_deref()
+        .ok_or_else(|| StoreError::NotFound(format!("entry {} has no TOTP secret", id)))?;
+    
+    let params = totp::parse(secret)?;
+    let account = if entry.username.is_empty() { &entry.name } else { &entry.username };
+    let uri = totp::to_uri(&params, &entry.name, account);
+    drop(store);
+    
+    totp::qr_svg(&uri)
+}
+
+#[tauri::command]
+async fn check_pwned(state: ActiveVault, id: String, timeout_secs: Option<u64>) -> Result<PwnedResult, StoreError> {
+    state.ensure_unlocked()?;
+    
+    // Блокировка не должна пережить await, поэтому берется в отдельном блоке
+    let password = {
+        let store = state.passwords.read_recover();
+        store.get(&id)
+            .map(|entry| Zeroizing::new(entry.password.clone()))
+            .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?
+    };
+    
+    let timeout = Duration::from_secs(timeout_secs.unwrap_or(DEFAULT_HTTP_TIMEOUT_SECS));
+    run_blocking(move || pwned::check(&password, timeout)).await
+}
+
+// Группируем id по совпадающему паролю; сами пароли наружу не попадают
+#[tauri::command]
+fn find_reused_passwords(state: ActiveVault) -> Result<Vec<Vec<String>>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    Ok(audit::reused_groups(store.values().filter(|e| !e.is_trashed())))
+}
+
+// Один проход по записям под одной блокировкой вместо нескольких вызовов счетчиков
+#[tauri::command]
+fn vault_stats(state: ActiveVault) -> Result<VaultStats, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    Ok(audit::stats(store.values(), audit::DEFAULT_WEAK_SCORE, now_unix()))
+}
+
+// Сводный отчет для панели безопасности; проверка по HIBP только по запросу,
+// так как требует сети
+#[tauri::command]
+async fn security_audit(
+    state: ActiveVault,
+    weak_score: Option<u8>,
+    max_age_days: Option<u64>,
+    check_pwned: Option<bool>,
+    timeout_secs: Option<u64>,
+) -> Result<AuditReport, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let options = audit::AuditOptions {
+        weak_score: weak_score.unwrap_or(audit::DEFAULT_WEAK_SCORE),
+        max_age_days: max_age_days.unwrap_or(audit::DEFAULT_MAX_AGE_DAYS),
+        now: now_unix(),
+    };
+    
+    let (mut report, passwords) = {
+        let store = state.passwords.read_recover();
+        // У карт и заметок пароля может не быть, проверять в них нечего
+        let entries: Vec<&PasswordEntry> = store
+            .values()
+            .filter(|e| !e.is_trashed() && !e.password.is_empty())
+            .collect();
+        let report = audit::audit(&entries, &options);
+        let passwords: Vec<(String, Zeroizing<String>)> = if check_pwned.unwrap_or(false) {
+            entries.iter().map(|e| (e.id.clone(), Zeroizing::new(e.password.clone()))).collect()
+        } else {
+            Vec::new()
+        };
+        (report, passwords)
+    };
+    
+    if !check_pwned.unwrap_or(false) {
+        return Ok(report);
+    }
+    
+    let timeout = Duration::from_secs(timeout_secs.unwrap_or(DEFAULT_HTTP_TIMEOUT_SECS));
+    run_blocking(move || {
+        audit::check_pwned(&mut report, &passwords, timeout)?;
+        Ok(report)
+    })
+    .await
+}
+
+// Пароль не возвращается в webview: он кладется в буфер обмена и стирается по таймауту,
+// но только если пользователь за это время не скопировал что-то свое
+#[tauri::command]
+fn copy_password_to_clipboard(
+    app: AppHandle,
+    clipboard: State<ClipboardGuard>,
+    state: ActiveVault,
+    id: String,
+    clear_after_secs: Option<u64>,
+) -> Result<u64, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let entry = store.get(&id)
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    state.ensure_reauthorized(entry)?;
+    let password = Zeroizing::new(entry.password.clone());
+    drop(store);
+    
+    let clear_after_secs = clear_after_secs.unwrap_or_else(|| clipboard.clear_after_secs());
+    let clear_after = (clear_after_secs > 0).then(|| Duration::from_secs(clear_after_secs));
+    clipboard.copy(&app, password, clear_after)?;
+    Ok(clear_after_secs)
+}
+
+#[tauri::command]
+fn get_clipboard_clear_secs(clipboard: State<ClipboardGuard>) -> u64 {
+    clipboard.clear_after_secs()
+}
+
+// 0 отключает фоновую очистку совсем: скопированный пароль остается в буфере,
+// где его могут прочитать другие программы и менеджеры истории буфера.
+// При выходе из приложения буфер все равно очищается
+#[tauri::command]
+fn set_clipboard_clear_secs(clipboard: State<ClipboardGuard>, secs: u64) -> Result<(), StoreError> {
+    let app_dir = config::app_data_dir();
+    let mut config = config::load(&app_dir);
+    config.clipboard_clear_secs = secs;
+    config::save(&app_dir, &config)?;
+    
+    clipboard.set_clear_after_secs(secs);
+    Ok(())
+}
+
+// Удаление переносит запись в корзину, окончательно ее убирают empty_trash/purge
+#[tauri::command]
+fn delete_password(state: ActiveVault, id: String) -> Result<(), StoreError> {
+    state.ensure_unlocked()?;
+    state.move_to_trash(&id)
+}
+
+// Возвращает запись, измененную последним update_password/delete_password,
+// в прежнее состояние. Более ранние изменения не отменяются, а другие
+// операции (добавление, импорт, корзина) снимок не трогают. Повторный
+// вызов ничего не делает и возвращает None
+#[tauri::command]
+fn undo_last(state: ActiveVault) -> Result<Option<String>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let Some(snapshot) = state.undo_snapshot.lock_recover().take() else {
+        return Ok(None);
+    };
+    let id = snapshot.id.clone();
+    state.passwords.write_recover().insert(id.clone(), snapshot);
+    
+    state.notify_changed(vec![id.clone()]);
+    
+    Ok(Some(id))
+}
+
+#[derive(Debug, Default, Serialize)]
+struct DeleteSummary {
+    deleted: usize,
+    not_found: usize,
+}
+
+// Все записи переносятся в корзину под одной блокировкой и одной записью на диск
+#[tauri::command]
+fn delete_passwords(state: ActiveVault, ids: Vec<String>) -> Result<DeleteSummary, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let now = now_unix();
+    let mut summary = DeleteSummary::default();
+    let mut deleted = Vec::new();
+    let mut store = state.passwords.write_recover();
+    for id in ids {
+        match store.get_mut(&id).filter(|entry| !entry.is_trashed()) {
+            Some(entry) => {
+                entry.deleted_at = Some(now);
+                deleted.push(id);
+            }
+            None => summary.not_found += 1,
+        }
+    }
+    summary.deleted = deleted.len();
+    
+    drop(store);
+    if !deleted.is_empty() {
+        state.notify_changed(deleted);
+    }
+    
+    Ok(summary)
+}
+
+#[tauri::command]
+fn restore_password(state: ActiveVault, id: String) -> Result<(), StoreError> {
+    state.ensure_unlocked()?;
+    
+    let mut store = state.passwords.write_recover();
+    let entry = store.get_mut(&id)
+        .filter(|entry| entry.is_trashed())
+        .ok_or_else(|| StoreError::NotFound(format!("no trashed entry with id {}", id)))?;
+    entry.deleted_at = None;
+    
+    drop(store);
+    state.notify_changed(vec![id]);
+    
+    Ok(())
+}
+
+#[tauri::command]
+fn list_trash(state: ActiveVault) -> Result<Vec<PasswordEntry>, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    Ok(store.values().filter(|e| e.is_trashed()).map(PasswordEntry::for_display).collect())
+}
+
+#[tauri::command]
+fn empty_trash(state: ActiveVault) -> Result<usize, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let mut store = state.passwords.write_recover();
+    let before = store.len();
+    store.retain(|_, entry| !entry.is_trashed());
+    let removed = before - store.len();
+    
+    drop(store);
+    if removed > 0 {
+        state.notify_changed(Vec::new());
+    }
+    
+    Ok(removed)
+}
+
+#[tauri::command]
+fn purge_trash_older_than(state: ActiveVault, days: u64) -> Result<usize, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let removed = state.purge_trash_older_than(days)?;
+    if removed > 0 {
+        state.notify_changed(Vec::new());
+    }
+    
+    Ok(removed)
+}
+
+fn import_csv_with_layout(
+    state: &PasswordStore,
+    path: &str,
+    layout: &import::CsvLayout,
+    strategy: MergeStrategy,
+) -> Result<ImportSummary, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let file = fs::File::open(path)
+        .map_err(|e| StoreError::Io(format!("Failed to open CSV file: {}", e)))?;
+    let parsed = import::parse_csv(file, layout)?;
+    Ok(merge_import(state, parsed, strategy))
+}
+
+fn merge_import(state: &PasswordStore, parsed: import::ParsedImport, strategy: MergeStrategy) -> ImportSummary {
+    let history_size = *state.password_history_size.lock_recover();
+    let mut store = state.passwords.write_recover();
+    let summary = import::merge(&mut store, parsed, strategy, history_size);
+    
+    drop(store);
+    if summary.imported > 0 || summary.overwritten > 0 {
+        state.notify_changed(Vec::new());
+    }
+    
+    summary
+}
+
+// Без strategy действует KeepBoth
+#[tauri::command]
+fn import_csv(
+    state: ActiveVault,
+    path: String,
+    strategy: Option<MergeStrategy>,
+) -> Result<ImportSummary, StoreError> {
+    import_csv_with_layout(&state, &path, &import::GENERIC_LAYOUT, strategy.unwrap_or_default())
+}
+
+#[tauri::command]
+fn import_bitwarden_json(
+    state: ActiveVault,
+    path: String,
+    strategy: Option<MergeStrategy>,
+) -> Result<ImportSummary, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let file = fs::File::open(&path)
+        .map_err(|e| StoreError::Io(format!("Failed to open Bitwarden export: {}", e)))?;
+    let parsed = import::parse_bitwarden_json(file)?;
+    Ok(merge_import(&state, parsed, strategy.unwrap_or_default()))
+}
+
+#[tauri::command]
+fn import_keepass_csv(
+    state: ActiveVault,
+    path: String,
+    strategy: Option<MergeStrategy>,
+) -> Result<ImportSummary, StoreError> {
+    import_csv_with_layout(&state, &path, &import::KEEPASS_LAYOUT, strategy.unwrap_or_default())
+}
+
+#[tauri::command]
+fn export_csv(state: ActiveVault, path: String) -> Result<(), StoreError> {
+    state.ensure_unlocked()?;
+    let data = state.csv_export()?;
+    write_private(Path::new(&path), &data)
+}
+
+// Фраза задается для каждого экспорта отдельно и к мастер-паролю не привязана
+#[tauri::command]
+fn export_encrypted(state: ActiveVault, path: String, passphrase: String) -> Result<(), StoreError> {
+    state.ensure_unlocked()?;
+    let passphrase = Zeroizing::new(passphrase);
+    
+    let report = strength::estimate(&passphrase);
+    if report.score < MIN_MASTER_PASSWORD_SCORE {
+        return Err(StoreError::Validation(format!(
+            "Export passphrase is too weak: {}",
+            report.warnings.join("; ")
+        )));
+    }
+    
+    let store = state.passwords.read_recover();
+    state.ensure_reauthorized_all(store.values().filter(|entry| !entry.is_trashed()))?;
+    let entries: HashMap<String, PasswordEntry> = store
+        .iter()
+        .filter(|(_, entry)| !entry.is_trashed())
+        .map(|(id, entry)| (id.clone(), entry.clone()))
+        .collect();
+    drop(store);
+    
+    write_private(Path::new(&path), &bundle::seal(&entries, &passphrase)?)
+}
+
+// Записи получают новые id, чтобы не затереть существующие с тем же ключом;
+// повторный импорт отсекается проверкой на дубликаты в merge
+#[tauri::command]
+fn import_encrypted(
+    state: ActiveVault,
+    path: String,
+    passphrase: String,
+    strategy: Option<MergeStrategy>,
+) -> Result<ImportSummary, StoreError> {
+    state.ensure_unlocked()?;
+    let passphrase = Zeroizing::new(passphrase);
+    
+    let data = fs::read(&path)
+        .map_err(|e| StoreError::Io(format!("Failed to read encrypted export: {}", e)))?;
+    let entries = bundle::open(&data, &passphrase)?;
+    
+    let parsed = import::ParsedImport {
+        entries: normalize_entries(entries)
+            .into_values()
+            .filter(|entry| !entry.is_trashed())
+            .map(|mut entry| {
+                entry.id = Uuid::new_v4().to_string();
+                entry
+            })
+            .collect(),
+        ..Default::default()
+    };
+    Ok(merge_import(&state, parsed, strategy.unwrap_or_default()))
+}
+
+// Сравнивает хранилище с файлом с другой машины, ничего не меняя.
+// Фраза нужна, если файл зашифрован: мастер-пароль той машины или фраза экспорта
+#[tauri::command]
+fn diff_vaults(state: ActiveVault, other_path: String, passphrase: Option<String>) -> Result<VaultDiff, StoreError> {
+    state.ensure_unlocked()?;
+    let passphrase = passphrase.map(Zeroizing::new);
+    
+    let data = fs::read(&other_path)
+        .map_err(|e| StoreError::Io(format!("Failed to read vault file: {}", e)))?;
+    let other = normalize_entries(merge::load_other(&data, passphrase.as_deref().map(String::as_str))?);
+    
+    let store = state.passwords.read_recover();
+    Ok(merge::diff(&store, &other))
+}
+
+// Объединяет записи другого файла с хранилищем. Результат шифруется и
+// записывается атомарно под блокировкой записей и только потом заменяет
+// их в памяти, так что при ошибке не меняется ни файл, ни хранилище.
+// Без strategy действует LastWriteWins
+#[tauri::command]
+fn merge_vault(
+    state: ActiveVault,
+    other_path: String,
+    strategy: Option<SyncStrategy>,
+    passphrase: Option<String>,
+) -> Result<MergeReport, StoreError> {
+    state.ensure_unlocked()?;
+    state.ensure_no_batch()?;
+    let passphrase = passphrase.map(Zeroizing::new);
+    
+    let data = fs::read(&other_path)
+        .map_err(|e| StoreError::Io(format!("Failed to read vault file: {}", e)))?;
+    let other = normalize_entries(merge::load_other(&data, passphrase.as_deref().map(String::as_str))?);
+    let history_size = *state.password_history_size.lock_recover();
+    
+    let mut passwords = state.passwords.write_recover();
+    let key = state.key.lock_recover();
+    let key_ref = key.as_ref().ok_or(StoreError::Locked)?;
+    let (merged, report) = merge::merge(&passwords, other, strategy.unwrap_or_default(), history_size);
+    if report.added == 0 && report.updated == 0 && report.kept_local == 0 {
+        return Ok(report);
+    }
+    
+    let encrypted = encrypt_entries(&merged, key_ref, state.compresses())?;
+    state.write_vault(&state.file_path(), &encrypted)?;
+    *passwords = merged;
+    state.dirty.store(false, Ordering::SeqCst);
+    state.undo_snapshot.lock_recover().take();
+    
+    drop(key);
+    drop(passwords);
+    state.emit(events::STORE_CHANGED, StoreChanged { ids: Vec::new() });
+    Ok(report)
+}
+
+// Если передан каталог, копия создается в нем с меткой времени в имени.
+// Заблокированное хранилище копируется с диска как есть — оно и так зашифровано,
+// а открыть копию можно только мастер-паролем
+#[tauri::command]
+fn create_backup(state: ActiveVault, path: String) -> Result<String, StoreError> {
+    let vault = if state.is_locked()? && !state.ephemeral {
+        fs::read(state.file_path())
+            .map_err(|e| StoreError::Io(format!("Error reading passwords file: {}", e)))?
+    } else {
+        state.touch()?;
+        state.ensure_reauthorized_all(state.passwords.read_recover().values())?;
+        state.encrypted_snapshot()?
+    };
+    
+    let created_at = now_unix();
+    let mut target = PathBuf::from(path);
+    if target.is_dir() {
+        target = target.join(format!("passwords-backup-{}.json", created_at));
+    }
+    
+    write_private(&target, &backup::encode(&vault, created_at)?)?;
+    Ok(target.to_string_lossy().into_owned())
+}
+
+// Заменяет хранилище содержимым копии и блокирует его: дальше нужен
+// мастер-пароль, с которым копия была создана
+#[tauri::command]
+fn restore_backup(state: ActiveVault, path: String, confirm: bool) -> Result<(), StoreError> {
+    if !confirm {
+        return Err(StoreError::Validation("Restoring a backup replaces the vault and must be confirmed".to_string()));
+    }
+    state.ensure_persistent()?;
+    state.ensure_no_batch()?;
+    
+    let data = fs::read(&path)
+        .map_err(|e| StoreError::Io(format!("Failed to read backup file: {}", e)))?;
+    state.restore_vault(&backup::decode(&data)?)
+}
+
+// Без length берется длина из настроек по умолчанию, наборы символов — всегда из них
+#[tauri::command]
+fn generate_password(state: ActiveVault, length: Option<u8>) -> Result<GeneratedPassword, StoreError> {
+    let settings = state.generation_settings.lock_recover().clone();
+    let length = length.map_or(settings.length, usize::from);
+    let options = settings.options();
+    state.generate_unique(|| generator::generate(length, &options))
+}
+
+#[tauri::command]
+fn get_generation_limits() -> GenerationLimits {
+    generator::LIMITS
+}
+
+#[tauri::command]
+fn get_default_generation_settings(state: ActiveVault) -> GenerationSettings {
+    state.generation_settings.lock_recover().clone()
+}
+
+#[tauri::command]
+fn set_default_generation_settings(
+    manager: State<VaultManager>,
+    state: ActiveVault,
+    settings: GenerationSettings,
+) -> Result<(), StoreError> {
+    settings.validate()?;
+    
+    if !state.ephemeral {
+        let app_dir = config::app_data_dir();
+        let mut config = config::load(&app_dir);
+        config.generation = settings.clone();
+        config::save(&app_dir, &config)?;
+    }
+    // Настройки общие для всех хранилищ
+    for store in manager.all() {
+        *store.generation_settings.lock_recover() = settings.clone();
+    }
+    Ok(())
+}
+
+// Проверяет записи в памяти и исправляет id так же, как при загрузке.
+// Исправления сохраняются обычным порядком, через notify_changed
+#[tauri::command]
+fn verify_integrity(state: ActiveVault) -> Result<IntegrityReport, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let mut passwords = state.passwords.write_recover();
+    let (repaired, report) = integrity::repair(std::mem::take(&mut *passwords));
+    *passwords = repaired;
+    drop(passwords);
+    
+    if !report.is_clean() {
+        state.notify_changed(report.changed_ids());
+    }
+    Ok(report)
+}
+
+// Режим общий для всех хранилищ. Включение сразу запечатывает записи
+// разблокированных хранилищ, выключение — расшифровывает обратно
+#[tauri::command]
+fn set_lazy_decryption(manager: State<VaultManager>, enabled: bool) -> Result<(), StoreError> {
+    let app_dir = config::app_data_dir();
+    let mut config = config::load(&app_dir);
+    config.lazy_decryption = enabled;
+    config::save(&app_dir, &config)?;
+    
+    for store in manager.all() {
+        store.passwords.set_lazy(enabled)?;
+    }
+    Ok(())
+}
+
+// Тоже общая настройка. Уже записанные файлы не трогаются: новый режим
+// применяется при следующем сохранении или сразу через rewrite_vault
+#[tauri::command]
+fn set_vault_compression(manager: State<VaultManager>, enabled: bool) -> Result<(), StoreError> {
+    let app_dir = config::app_data_dir();
+    let mut config = config::load(&app_dir);
+    config.compress_vault = enabled;
+    config::save(&app_dir, &config)?;
+    
+    for store in manager.all() {
+        store.set_compress(enabled);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn generate_password_advanced(
+    state: ActiveVault,
+    length: u8,
+    uppercase: bool,
+    lowercase: bool,
+    digits: bool,
+    symbols: bool,
+    exclude_ambiguous: Option<bool>,
+    no_repeats: Option<bool>,
+    no_sequences: Option<bool>,
+) -> Result<GeneratedPassword, StoreError> {
+    let options = GeneratorOptions {
+        uppercase,
+        lowercase,
+        digits,
+        symbols,
+        exclude_ambiguous: exclude_ambiguous.unwrap_or(false),
+        no_repeats: no_repeats.unwrap_or(false),
+        no_sequences: no_sequences.unwrap_or(false),
+    };
+    state.generate_unique(|| generator::generate(length as usize, &options))
+}
+
+#[tauri::command]
+fn generate_for_policy(state: ActiveVault, policy: PasswordPolicy) -> Result<GeneratedPassword, StoreError> {
+    state.generate_unique(|| generator::generate_for_policy(&policy))
+}
+
+// Новый пароль для записи по ее политике; без политики — набор по умолчанию
+#[tauri::command]
+fn generate_for_entry(state: ActiveVault, id: String, length: Option<u8>) -> Result<GeneratedPassword, StoreError> {
+    state.ensure_unlocked()?;
+    
+    let store = state.passwords.read_recover();
+    let policy = store.get(&id)
+        .map(|entry| entry.policy.clone())
+        .ok_or_else(|| StoreError::NotFound(format!("no entry with id {}", id)))?;
+    drop(store);
+    
+    match policy {
+        Some(policy) => state.generate_unique(|| generator::generate_for_policy(&policy)),
+        None => {
+            let length = length.map_or(generator::DEFAULT_LENGTH, usize::from);
+            state.generate_unique(|| Ok(generator::generate_default(length)))
+        }
+    }
+}
+
+#[tauri::command]
+fn get_generation_history(state: ActiveVault) -> Result<Vec<String>, StoreError> {
+    state.ensure_unlocked()?;
+    let history = state.generation_history.lock_recover();
+    Ok(history.iter().map(|password| password.to_string()).collect())
+}
+
+#[tauri::command]
+fn clear_generation_history(state: ActiveVault) -> Result<(), StoreError> {
+    state.generation_history.lock_recover().clear();
+    Ok(())
+}
+
+#[tauri::command]
+fn generate_passphrase(
+    words: Option<u8>,
+    separator: Option<String>,
+    capitalize: bool,
+    add_number: bool,
+) -> Result<GeneratedPassword, StoreError> {
+    generator::generate_passphrase(
+        words.unwrap_or(generator::DEFAULT_PASSPHRASE_WORDS),
+        separator.as_deref().unwrap_or("-"),
+        capitalize,
+        add_number,
+    )
+}
+
+#[tauri::command]
+fn estimate_strength(password: String) -> StrengthReport {
+    strength::estimate(&password)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let context = tauri::generate_context!();
+    
+    tauri::Builder::default()
+        // Логгер подключается первым, чтобы в журнал попали и ошибки открытия хранилища.
+        // Секреты в журнал не пишутся: Debug записей их маскирует
+        .plugin(
+            tauri_plugin_log::Builder::new()
+                .level(log::LevelFilter::Info)
+                .build(),
+        )
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .setup(|app| {
+            // Инициализируем хранилища паролей
+            let manager = VaultManager::open();
+            manager.set_app_handle(app.handle().clone());
+            
+            // Только с --headless-unlock; пароль затирается сразу после вывода ключа
+            if let Some(master_password) = headless::master_password() {
+                match manager.active().unlock(&master_password) {
+                    Ok(()) => log::info!("Vault unlocked at startup"),
+                    Err(e) => log::error!("Automatic unlock failed: {}", e),
+                }
+            }
+            app.manage(manager);
+            app.manage(ClipboardGuard::new(config::load(&config::app_data_dir()).clipboard_clear_secs));
+            
+            // Фоновая проверка неактивности для автоблокировки и истекших показов паролей
+            let handle = app.handle().clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(1));
+                for store in handle.state::<VaultManager>().all() {
+                    if let Err(e) = store.lock_if_idle() {
+                        log::error!("Auto-lock check failed: {}", e);
+                    }
+                    store.expire_reveals();
+                }
+            });
+            
+            // Фоновая запись накопленных изменений
+            let handle = app.handle().clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_millis(FLUSH_INTERVAL_MS));
+                for store in handle.state::<VaultManager>().all() {
+                    if let Err(e) = store.flush() {
+                        log::error!("Failed to flush password store: {}", e);
+                    }
+                }
+            });
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            unlock,
+            change_master_password,
+            generate_recovery_key,
+            confirm_identity,
+            destroy_vault,
+            unlock_with_recovery_key,
+            get_kdf_params,
+            set_kdf_params,
+            rewrite_vault,
+            set_vault_path,
+            vault_status,
+            list_vaults,
+            create_vault,
+            switch_vault,
+            delete_vault,
+            get_corrupt_vault_path,
+            dismiss_corrupt_vault,
+            watch_vault,
+            is_locked,
+            flush,
+            begin_batch,
+            end_batch,
+            lock,
+            auto_lock_after,
+            password_history_size,
+            add_password,
+            add_generated,
+            duplicate_password,
+            list_templates,
+            save_template,
+            delete_template,
+            create_from_template,
+            update_password,
+            patch_password,
+            get_passwords,
+            get_entries_metadata,
+            get_password,
+            get_passwords_by_ids,
+            reveal_password,
+            mark_hidden,
+            get_password_history,
+            reveal_notes,
+            attach_file,
+            list_attachments,
+            get_attachment,
+            remove_attachment,
+            get_entry_domain,
+            mark_used,
+            get_recently_used,
+            get_favorites,
+            toggle_favorite,
+            search_passwords,
+            get_expiring_passwords,
+            get_passwords_by_tag,
+            list_all_tags,
+            tag_summary,
+            add_tag_to,
+            remove_tag_from,
+            rename_tag,
+            count_passwords,
+            count_by_tag,
+            find_reused_passwords,
+            security_audit,
+            vault_stats,
+            get_totp_code,
+            totp_qr,
+            check_pwned,
+            copy_password_to_clipboard,
+            get_clipboard_clear_secs,
+            set_clipboard_clear_secs,
+            delete_password,
+            delete_passwords,
+            undo_last,
+            restore_password,
+            list_trash,
+            empty_trash,
+            purge_trash_older_than,
+            import_csv,
+            import_keepass_csv,
+            import_bitwarden_json,
+            export_csv,
+            export_encrypted,
+            import_encrypted,
+            diff_vaults,
+            merge_vault,
+            create_backup,
+            restore_backup,
+            generate_password,
+            get_generation_limits,
+            get_default_generation_settings,
+            set_default_generation_settings,
+            set_lazy_decryption,
+            set_vault_compression,
+            verify_integrity,
+            generate_password_advanced,
+            generate_for_policy,
+            generate_for_entry,
+            generate_passphrase,
+            get_generation_history,
+            clear_generation_history,
+            estimate_strength
+        ])
+        .build(context)
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                // Пароль не должен остаться в буфере после закрытия приложения
+                app.state::<ClipboardGuard>().clear_if_ours(app, None);
+                // Несохраненные изменения пишутся синхронно. Запись атомарная
+                // (временный файл и rename), так что даже прерванный выход
+                // оставляет на диске прежнюю или новую версию целиком
+                for store in app.state::<VaultManager>().all() {
+                    store.close();
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn entry(name: &str, password: &str) -> PasswordEntry {
+        PasswordEntry::new(name.to_string(), "user".to_string(), password.to_string(), None, None)
+    }
+    
+    #[test]
+    fn for_display_drops_password_history() {
+        let mut original = entry("mail", "old-password");
+        let mut updated = original.clone();
+        updated.password = "new-password".to_string();
+        original.replace_with(updated, DEFAULT_PASSWORD_HISTORY_SIZE);
+        assert_eq!(original.history.len(), 1);
+        
+        let shown = original.for_display();
+        assert!(shown.history.is_empty());
+        assert_eq!(shown.password, "new-password");
+    }
+    
+    #[test]
+    fn for_display_hides_reauth_secrets_until_restored() {
+        let mut original = entry("bank", "secret-password");
+        original.require_reauth = true;
+        original.totp_secret = Some("JBSWY3DPEHPK3PXP".to_string());
+        original.custom_fields = vec![
+            CustomField { label: "pin".to_string(), value: "1234".to_string(), secret: true },
+            CustomField { label: "branch".to_string(), value: "north".to_string(), secret: false },
+        ];
+        
+        let mut shown = original.for_display();
+        assert!(shown.password.is_empty());
+        assert!(shown.totp_secret.is_none());
+        assert!(shown.custom_fields[0].value.is_empty());
+        assert_eq!(shown.custom_fields[1].value, "north");
+        
+        shown.restore_hidden(&original);
+        assert_eq!(shown.password, "secret-password");
+        assert_eq!(shown.totp_secret, original.totp_secret);
+        assert_eq!(shown.custom_fields, original.custom_fields);
+    }
+    
+    // Дешевые параметры KDF: unlock перечитывает их из заголовка, и тесты
+    // не тратят секунды на каждый вывод ключа
+    const TEST_KDF: KdfParams = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 };
+    
+    // Как первый unlock нового хранилища, но с TEST_KDF
+    fn set_up(store: &PasswordStore, master_password: &str) {
+        let key = crypto::derive_key(master_password, TEST_KDF, crypto::generate_salt()).unwrap();
+        *store.key.lock_recover() = Some(key);
+        store.save_to_disk().unwrap();
+    }
+    
+    const MASTER_PASSWORD: &str = "correct horse battery";
+    
+    fn open_store(dir: &Path) -> PasswordStore {
+        PasswordStore::open("test", dir, dir, GenerationSettings::default()).unwrap()
+    }
+    
+    fn add(store: &PasswordStore, entry: PasswordEntry) -> String {
+        let id = entry.id.clone();
+        store.passwords.write_recover().insert(id.clone(), entry);
+        store.notify_changed(vec![id.clone()]);
+        id
+    }
+    
+    #[test]
+    fn in_memory_lock_keeps_entries_for_the_same_password() {
+        let store = PasswordStore::in_memory();
+        set_up(&store, MASTER_PASSWORD);
+        let id = add(&store, entry("mail", "secret"));
+        
+        store.lock().unwrap();
+        assert!(store.is_locked().unwrap());
+        assert!(store.passwords.read_recover().is_empty());
+        assert!(matches!(store.unlock("wrong password"), Err(StoreError::IncorrectPassword)));
+        
+        store.unlock(MASTER_PASSWORD).unwrap();
+        assert_eq!(store.passwords.read_recover()[&id].password, "secret");
+    }
+    
+    #[test]
+    fn degraded_store_skips_disk_and_survives_auto_lock() {
+        let store = PasswordStore::degraded("disk is full".to_string());
+        assert_eq!(store.degraded_reason.as_deref(), Some("disk is full"));
+        assert!(store.ephemeral);
+        assert!(store.file_path().as_os_str().is_empty());
+        assert!(store.ensure_persistent().is_err());
+        
+        set_up(&store, MASTER_PASSWORD);
+        let id = add(&store, entry("mail", "secret"));
+        // Запись по пустому пути завершилась бы ошибкой
+        store.flush().unwrap();
+        
+        *store.auto_lock_after.lock_recover() = Some(Duration::ZERO);
+        assert!(store.lock_if_idle().unwrap());
+        assert!(store.is_locked().unwrap());
+        
+        store.unlock(MASTER_PASSWORD).unwrap();
+        assert!(store.passwords.read_recover().contains_key(&id));
+    }
+    
+    #[test]
+    fn interrupted_save_leaves_previous_vault_loadable() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(dir.path());
+        set_up(&store, MASTER_PASSWORD);
+        let id = add(&store, entry("mail", "secret"));
+        store.flush().unwrap();
+        
+        // Процесс упал посреди записи: временный файл обрезан, до rename не дошло
+        add(&store, entry("bank", "other"));
+        let mut next = store.encrypted_snapshot().unwrap();
+        next.truncate(next.len() / 2);
+        fs::write(dir.path().join("passwords.json.tmp"), &next).unwrap();
+        drop(store);
+        
+        let store = open_store(dir.path());
+        store.unlock(MASTER_PASSWORD).unwrap();
+        let passwords = store.passwords.read_recover();
+        assert_eq!(passwords.len(), 1);
+        assert_eq!(passwords[&id].password, "secret");
+    }
+    
+    // Поле, на которое указывает ошибка проверки: "url: ..." -> "url"
+    fn rejected_field(mut entry: PasswordEntry) -> String {
+        match validate_entry(&mut entry) {
+            Err(StoreError::Validation(reason)) => reason.split(':').next().unwrap().to_string(),
+            other => panic!("expected a validation error, got {:?}", other.map(|_| ())),
+        }
+    }
+    
+    fn card(number: &str) -> CardDetails {
+        CardDetails { holder: "A HOLDER".to_string(), number: number.to_string(), expiry: None, cvv: None }
+    }
+    
+    #[test]
+    fn validation_rejects_each_invalid_field() {
+        assert_eq!(rejected_field(entry("   ", "secret")), "name");
+        assert_eq!(rejected_field(entry("mail", "  ")), "password");
+        
+        let mut card_entry = entry("visa", "");
+        card_entry.kind = EntryKind::Card;
+        assert_eq!(rejected_field(card_entry.clone()), "card");
+        card_entry.card = Some(card("4111 1111 1111 1112"));
+        assert_eq!(rejected_field(card_entry), "card.number");
+        let mut login_with_card = entry("mail", "secret");
+        login_with_card.card = Some(card("4111 1111 1111 1111"));
+        assert_eq!(rejected_field(login_with_card), "card");
+        
+        let mut bad_url = entry("mail", "secret");
+        bad_url.url = Some("https://".to_string());
+        assert_eq!(rejected_field(bad_url), "url");
+        
+        let mut unlabeled = entry("mail", "secret");
+        unlabeled.custom_fields = vec![CustomField { label: " ".to_string(), value: "x".to_string(), secret: false }];
+        assert_eq!(rejected_field(unlabeled), "custom_fields");
+        
+        let mut bad_totp = entry("mail", "secret");
+        bad_totp.totp_secret = Some("not base32!".to_string());
+        assert!(validate_entry(&mut bad_totp).is_err());
+        
+        let mut bad_policy = entry("mail", "secret");
+        bad_policy.policy = Some(PasswordPolicy {
+            min_length: 20,
+            max_length: 10,
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digits: false,
+            require_symbols: false,
+            forbidden: String::new(),
+        });
+        assert_eq!(rejected_field(bad_policy), "policy");
+    }
+    
+    #[test]
+    fn validation_drops_blank_optional_fields() {
+        let mut valid = entry("  mail  ", "secret");
+        valid.url = Some("  ".to_string());
+        valid.notes = Some(String::new());
+        valid.totp_secret = Some(" ".to_string());
+        validate_entry(&mut valid).unwrap();
+        assert_eq!(valid.name, "mail");
+        assert_eq!(valid.url, None);
+        assert_eq!(valid.notes, None);
+        assert_eq!(valid.totp_secret, None);
+    }
+    
+    #[test]
+    fn restoring_a_backup_brings_back_the_saved_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(dir.path());
+        set_up(&store, MASTER_PASSWORD);
+        let kept = add(&store, entry("mail", "secret"));
+        let backup = backup::encode(&store.encrypted_snapshot().unwrap(), now_unix()).unwrap();
+        
+        store.passwords.write_recover().remove(&kept);
+        let added = add(&store, entry("bank", "other"));
+        store.save_to_disk().unwrap();
+        
+        store.restore_vault(&backup::decode(&backup).unwrap()).unwrap();
+        assert!(store.is_locked().unwrap());
+        store.unlock(MASTER_PASSWORD).unwrap();
+        let passwords = store.passwords.read_recover();
+        assert_eq!(passwords[&kept].password, "secret");
+        assert!(!passwords.contains_key(&added));
+    }
+    
+    #[cfg(unix)]
+    fn mode(path: &Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn vault_files_are_private_to_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        
+        // Новый каталог хранилища и уже существующий с открытыми правами
+        let new_dir = dir.path().join("new-vault");
+        let old_dir = dir.path().join("old-vault");
+        fs::create_dir(&old_dir).unwrap();
+        fs::set_permissions(&old_dir, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(old_dir.join(config::VAULT_FILE_NAME), b"{}").unwrap();
+        fs::set_permissions(old_dir.join(config::VAULT_FILE_NAME), fs::Permissions::from_mode(0o644)).unwrap();
+        
+        for vault_dir in [&new_dir, &old_dir] {
+            let store = open_store(vault_dir);
+            set_up(&store, MASTER_PASSWORD);
+            assert_eq!(mode(vault_dir), 0o700);
+            assert_eq!(mode(&store.file_path()), 0o600);
+        }
+        
+        let export = dir.path().join("export.csv");
+        write_private(&export, b"name,password").unwrap();
+        assert_eq!(mode(&export), 0o600);
+    }
+    
+    #[test]
+    fn malformed_vault_is_set_aside_and_blocks_unlock() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = dir.path().join(config::VAULT_FILE_NAME);
+        fs::write(&vault, b"{\"mail\": {\"password\": ").unwrap();
+        
+        let store = open_store(dir.path());
+        let corrupt = store.corrupt_file.lock_recover().clone().unwrap();
+        assert!(!vault.exists());
+        assert!(corrupt.file_name().unwrap().to_string_lossy().starts_with("passwords.json.corrupt."));
+        assert_eq!(fs::read(&corrupt).unwrap(), b"{\"mail\": {\"password\": ");
+        
+        assert!(matches!(store.unlock(MASTER_PASSWORD), Err(StoreError::Corrupt(_))));
+        assert!(!vault.exists());
+    }
+    
+    #[test]
+    fn unsaved_changes_are_written_on_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(dir.path());
+        set_up(&store, MASTER_PASSWORD);
+        let id = add(&store, entry("mail", "secret"));
+        assert!(store.dirty.load(Ordering::SeqCst));
+        
+        store.close();
+        assert!(store.is_locked().unwrap());
+        drop(store);
+        
+        let reopened = open_store(dir.path());
+        reopened.unlock(MASTER_PASSWORD).unwrap();
+        assert_eq!(reopened.passwords.read_recover()[&id].password, "secret");
+    }
+    
+    // Расшифрованное содержимое файла хранилища, без распаковки
+    fn vault_plaintext(store: &PasswordStore) -> Vec<u8> {
+        let data = fs::read(store.file_path()).unwrap();
+        let key = store.key.lock_recover();
+        crypto::decrypt(key.as_ref().unwrap(), &data).unwrap().to_vec()
+    }
+    
+    #[test]
+    fn compressed_and_uncompressed_vaults_both_open() {
+        for compress in [true, false] {
+            let dir = tempfile::tempdir().unwrap();
+            let store = open_store(dir.path());
+            store.set_compress(compress);
+            set_up(&store, MASTER_PASSWORD);
+            let id = add(&store, entry("mail", "secret"));
+            store.save_to_disk().unwrap();
+            assert_eq!(vault_plaintext(&store).starts_with(&[0x1f, 0x8b]), compress);
+            store.close();
+            drop(store);
+            
+            let reopened = open_store(dir.path());
+            reopened.unlock(MASTER_PASSWORD).unwrap();
+            assert_eq!(reopened.passwords.read_recover()[&id].password, "secret");
+        }
+    }
+    
+    #[test]
+    fn rewrite_applies_the_current_compression_setting() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(dir.path());
+        store.set_compress(false);
+        set_up(&store, MASTER_PASSWORD);
+        let id = add(&store, entry("mail", "secret"));
+        store.save_to_disk().unwrap();
+        assert!(vault_plaintext(&store).starts_with(b"{"));
+        
+        store.set_compress(true);
+        store.rewrite().unwrap();
+        assert!(vault_plaintext(&store).starts_with(&[0x1f, 0x8b]));
+        
+        store.lock().unwrap();
+        store.unlock(MASTER_PASSWORD).unwrap();
+        assert_eq!(store.passwords.read_recover()[&id].password, "secret");
+    }
+    
+    #[test]
+    fn deleting_a_trashed_entry_keeps_its_deletion_time() {
+        let store = PasswordStore::in_memory();
+        set_up(&store, MASTER_PASSWORD);
+        let id = add(&store, entry("mail", "secret"));
+        store.move_to_trash(&id).unwrap();
+        
+        let deleted_at = now_unix() - 3600;
+        store.passwords.write_recover().get_mut(&id).unwrap().deleted_at = Some(deleted_at);
+        store.undo_snapshot.lock_recover().take();
+        store.move_to_trash(&id).unwrap();
+        assert_eq!(store.passwords.read_recover()[&id].deleted_at, Some(deleted_at));
+        assert!(store.undo_snapshot.lock_recover().is_none());
+    }
+    
+    #[test]
+    fn deleting_an_unknown_entry_is_not_found() {
+        let store = PasswordStore::in_memory();
+        set_up(&store, MASTER_PASSWORD);
+        store.dirty.store(false, Ordering::SeqCst);
+        assert!(matches!(store.move_to_trash("missing"), Err(StoreError::NotFound(_))));
+        assert!(!store.dirty.load(Ordering::SeqCst));
+    }
+    
+    #[test]
+    fn csv_export_of_reauth_entries_needs_a_grant() {
+        let store = PasswordStore::in_memory();
+        set_up(&store, MASTER_PASSWORD);
+        add(&store, entry("mail", "plain"));
+        let mut protected = entry("bank", "secret");
+        protected.require_reauth = true;
+        add(&store, protected);
+        
+        assert!(matches!(store.csv_export(), Err(StoreError::ReauthRequired)));
+        
+        store.confirm_identity(MASTER_PASSWORD).unwrap();
+        let csv = String::from_utf8(store.csv_export().unwrap()).unwrap();
+        assert!(csv.contains("secret"));
+    }
+    
+    #[test]
+    fn repeated_unlock_keeps_unsaved_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open_store(dir.path());
+        set_up(&store, MASTER_PASSWORD);
+        let id = add(&store, entry("mail", "secret"));
+        assert!(store.dirty.load(Ordering::SeqCst));
+        
+        store.unlock(MASTER_PASSWORD).unwrap();
+        assert_eq!(store.passwords.read_recover()[&id].password, "secret");
+        assert!(store.dirty.load(Ordering::SeqCst));
+    }
+    
+    #[test]
+    fn generation_history_is_kept_only_while_unlocked() {
+        let store = PasswordStore::in_memory();
+        let options = GenerationSettings::default().options();
+        store.generate_unique(|| generator::generate(generator::DEFAULT_LENGTH, &options)).unwrap();
+        assert!(store.generation_history.lock_recover().is_empty());
+        
+        set_up(&store, MASTER_PASSWORD);
+        let generated = store.generate_unique(|| generator::generate(generator::DEFAULT_LENGTH, &options)).unwrap();
+        let history = store.generation_history.lock_recover();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].as_str(), generated.value);
+    }
 }
@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::error::StoreError;
+use crate::{bundle, compress, crypto, schema, PasswordEntry, PasswordHistoryItem};
+
+// Как решать конфликт записи, измененной на обеих машинах
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum SyncStrategy {
+    // Побеждает версия с более поздним updated_at; при равных метках
+    // конфликт остается нерешенным и сохраняется локальная версия
+    #[default]
+    LastWriteWins,
+    PreferLocal,
+    PreferRemote,
+}
+
+// Какая из сторон конфликта изменена позже (по updated_at)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Newer {
+    Local,
+    Other,
+    // Метки совпадают, автоматически выбрать нельзя
+    Same,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffEntry {
+    pub id: String,
+    pub name: String,
+    pub username: String,
+    pub updated_at: u64,
+}
+
+impl DiffEntry {
+    fn from(entry: &PasswordEntry) -> Self {
+        Self {
+            id: entry.id.clone(),
+            name: entry.name.clone(),
+            username: entry.username.clone(),
+            updated_at: entry.updated_at,
+        }
+    }
+}
+
+// Запись есть с обеих сторон под одним id, но содержимое различается
+#[derive(Debug, Serialize)]
+pub struct Conflict {
+    pub id: String,
+    pub name: String,
+    pub fields: Vec<String>,
+    pub local_updated_at: u64,
+    pub other_updated_at: u64,
+    pub newer: Newer,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MergeReport {
+    // Записи, которых не было локально
+    pub added: usize,
+    // Конфликты, решенные в пользу другой стороны
+    pub updated: usize,
+    // Конфликты, решенные в пользу локальной версии
+    pub kept_local: usize,
+    pub unresolved: Vec<Conflict>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct VaultDiff {
+    pub only_local: Vec<DiffEntry>,
+    pub only_other: Vec<DiffEntry>,
+    pub conflicts: Vec<Conflict>,
+}
+
+// Другой файл может быть хранилищем (зашифрованным или старым открытым)
+// или зашифрованным экспортом; для зашифрованных нужна парольная фраза
+pub fn load_other(data: &[u8], passphrase: Option<&str>) -> Result<HashMap<String, PasswordEntry>, StoreError> {
+    let encrypted = crypto::is_encrypted(data) || bundle::is_bundle(data);
+    let passphrase = match passphrase {
+        Some(passphrase) => passphrase,
+        None if encrypted => {
+            return Err(StoreError::Validation("A passphrase is required to read an encrypted vault".to_string()));
+        }
+        None => "",
+    };
+
+    if bundle::is_bundle(data) {
+        return bundle::open(data, passphrase);
+    }
+    if crypto::is_encrypted(data) {
+        let key = crypto::derive_key_for(passphrase, data)?;
+        let plaintext = compress::decompress(&crypto::decrypt(&key, data)?)?;
+        return Ok(schema::load(&plaintext)?.entries);
+    }
+    Ok(schema::load(data)?.entries)
+}
+
+// Поля, которыми различаются две версии одной записи. Корзина тоже
+// считается изменением: запись удалили на одной из машин
+pub fn differing_fields(local: &PasswordEntry, other: &PasswordEntry) -> Vec<String> {
+    let mut fields = local.changed_fields(other);
+    if local.deleted_at.is_some() != other.deleted_at.is_some() {
+        fields.push("deleted_at".to_string());
+    }
+    fields
+}
+
+pub fn newer(local: &PasswordEntry, other: &PasswordEntry) -> Newer {
+    match local.updated_at.cmp(&other.updated_at) {
+        std::cmp::Ordering::Greater => Newer::Local,
+        std::cmp::Ordering::Less => Newer::Other,
+        std::cmp::Ordering::Equal => Newer::Same,
+    }
+}
+
+pub fn diff(local: &HashMap<String, PasswordEntry>, other: &HashMap<String, PasswordEntry>) -> VaultDiff {
+    let mut result = VaultDiff::default();
+
+    for (id, entry) in local {
+        match other.get(id) {
+            None => result.only_local.push(DiffEntry::from(entry)),
+            Some(theirs) => {
+                let fields = differing_fields(entry, theirs);
+                if !fields.is_empty() {
+                    result.conflicts.push(Conflict {
+                        id: id.clone(),
+                        name: entry.name.clone(),
+                        fields,
+                        local_updated_at: entry.updated_at,
+                        other_updated_at: theirs.updated_at,
+                        newer: newer(entry, theirs),
+                    });
+                }
+            }
+        }
+    }
+    result.only_other = other
+        .iter()
+        .filter(|(id, _)| !local.contains_key(*id))
+        .map(|(_, entry)| DiffEntry::from(entry))
+        .collect();
+
+    result.only_local.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    result.only_other.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    result.conflicts.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    result
+}
+
+// Объединенная версия записи: поля победителя, история обеих сторон и
+// пароль проигравшей стороны как элемент истории, чтобы он не потерялся
+fn combine(winner: &PasswordEntry, loser: &PasswordEntry, history_size: usize) -> PasswordEntry {
+    let mut entry = winner.clone();
+
+    let mut history: Vec<PasswordHistoryItem> = winner.history.iter().chain(&loser.history).cloned().collect();
+    if !crypto::secrets_equal(&loser.password, &winner.password) {
+        history.push(PasswordHistoryItem {
+            password: loser.password.clone(),
+            changed_at: loser.updated_at,
+        });
+    }
+    history.sort_by_key(|item| std::cmp::Reverse(item.changed_at));
+    history.dedup_by(|a, b| a.changed_at == b.changed_at && crypto::secrets_equal(&a.password, &b.password));
+    history.truncate(history_size);
+    entry.history = history;
+
+    let attachment_ids: HashSet<&str> = winner.attachments.iter().map(|a| a.id.as_str()).collect();
+    let missing: Vec<_> = loser
+        .attachments
+        .iter()
+        .filter(|a| !attachment_ids.contains(a.id.as_str()))
+        .cloned()
+        .collect();
+    entry.attachments.extend(missing);
+    entry.last_used_at = winner.last_used_at.max(loser.last_used_at);
+    entry
+}
+
+// Строит объединенное хранилище, не трогая локальное: вызывающий код
+// записывает результат на диск и только потом подменяет им записи в памяти
+pub fn merge(
+    local: &HashMap<String, PasswordEntry>,
+    other: HashMap<String, PasswordEntry>,
+    strategy: SyncStrategy,
+    history_size: usize,
+) -> (HashMap<String, PasswordEntry>, MergeReport) {
+    let mut merged = local.clone();
+    let mut report = MergeReport::default();
+
+    for (id, theirs) in other {
+        let Some(ours) = local.get(&id) else {
+            merged.insert(id, theirs);
+            report.added += 1;
+            continue;
+        };
+        let fields = differing_fields(ours, &theirs);
+        if fields.is_empty() {
+            continue;
+        }
+
+        let newer = newer(ours, &theirs);
+        let take_theirs = match strategy {
+            SyncStrategy::PreferLocal => false,
+            SyncStrategy::PreferRemote => true,
+            SyncStrategy::LastWriteWins if newer == Newer::Same => {
+                report.unresolved.push(Conflict {
+                    id: id.clone(),
+                    name: ours.name.clone(),
+                    fields,
+                    local_updated_at: ours.updated_at,
+                    other_updated_at: theirs.updated_at,
+                    newer,
+                });
+                continue;
+            }
+            SyncStrategy::LastWriteWins => newer == Newer::Other,
+        };
+
+        let combined = if take_theirs {
+            report.updated += 1;
+            combine(&theirs, ours, history_size)
+        } else {
+            report.kept_local += 1;
+            combine(ours, &theirs, history_size)
+        };
+        merged.insert(id, combined);
+    }
+
+    report.unresolved.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    (merged, report)
+}
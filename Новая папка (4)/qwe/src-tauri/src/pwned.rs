@@ -0,0 +1,82 @@
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::error::Error;
+use std::io::{self, Read};
+use std::time::Duration;
+
+use crate::error::StoreError;
+
+const RANGE_API: &str = "https://api.pwnedpasswords.com/range/";
+
+#[derive(Debug, Serialize)]
+pub struct PwnedResult {
+    pub pwned: bool,
+    pub count: u64,
+    // Сеть недоступна — результат неизвестен, это не ошибка
+    pub offline: bool,
+}
+
+// ureq оборачивает ошибку сокета, поэтому ищем ее по цепочке source
+fn is_timeout(error: &(dyn Error + 'static)) -> bool {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(io_error) = error.downcast_ref::<io::Error>() {
+            if matches!(io_error.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) {
+                return true;
+            }
+        }
+        current = error.source();
+    }
+    false
+}
+
+// k-anonymity: в сеть уходят только первые пять символов SHA-1,
+// совпадение по остатку хеша ищется локально.
+// Превышение таймаута — ошибка Timeout, прочие сбои сети дают offline
+pub fn check(password: &str, timeout: Duration) -> Result<PwnedResult, StoreError> {
+    let hash: String = Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    let (prefix, suffix) = hash.split_at(5);
+
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    let mut body = String::new();
+    let response = agent
+        .get(&format!("{}{}", RANGE_API, prefix))
+        .set("Add-Padding", "true")
+        .call();
+
+    let read = response
+        .map_err(|e| (is_timeout(&e), e.to_string()))
+        .and_then(|r| {
+            r.into_reader()
+                .read_to_string(&mut body)
+                .map_err(|e| (is_timeout(&e), e.to_string()))
+        });
+    match read {
+        Ok(_) => {}
+        Err((true, _)) => return Err(StoreError::Timeout),
+        Err((false, e)) => {
+            log::warn!("HIBP range request failed: {}", e);
+            return Ok(PwnedResult {
+                pwned: false,
+                count: 0,
+                offline: true,
+            });
+        }
+    }
+
+    let count = body
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(candidate, _)| candidate.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+
+    Ok(PwnedResult {
+        pwned: count > 0,
+        count,
+        offline: false,
+    })
+}
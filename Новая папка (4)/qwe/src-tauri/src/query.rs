@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::PasswordEntry;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum SortField {
+    #[default]
+    Name,
+    Username,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+}
+
+fn compare(a: &PasswordEntry, b: &PasswordEntry, field: SortField) -> Ordering {
+    match field {
+        SortField::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        SortField::Username => a.username.to_lowercase().cmp(&b.username.to_lowercase()),
+        SortField::CreatedAt => a.created_at.cmp(&b.created_at),
+        SortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+    }
+}
+
+// При равенстве ключа порядок определяет id, чтобы страницы не "прыгали"
+// между запросами: HashMap отдает записи в произвольном порядке
+pub fn sort(entries: &mut [PasswordEntry], field: SortField, ascending: bool, favorites_first: bool) {
+    entries.sort_by(|a, b| {
+        let favorites = if favorites_first {
+            b.is_favorite.cmp(&a.is_favorite)
+        } else {
+            Ordering::Equal
+        };
+        let mut order = compare(a, b, field).then_with(|| a.id.cmp(&b.id));
+        if !ascending {
+            order = order.reverse();
+        }
+        favorites.then(order)
+    });
+}
+
+// Без limit возвращаются все записи начиная с offset
+pub fn paginate<T>(items: Vec<T>, offset: usize, limit: Option<usize>) -> Page<T> {
+    let total = items.len();
+    let items = items
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    Page { items, total }
+}
+
+#[derive(Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub entry: PasswordEntry,
+    // Чем больше, тем лучше совпадение; для пустого запроса 0
+    pub score: u32,
+}
+
+// Кратка в "й" — часть отдельной буквы, а не ударение: "Мой" и "Мои" разные слова
+const BREVE: char = '\u{306}';
+
+// Форма для сравнения при поиске: NFKD без диакритических знаков и в нижнем
+// регистре, так что "Café" находится по "cafe", а "Ёлка" — по "елка".
+// Совместимая декомпозиция заодно сводит полноширинные буквы и лигатуры к обычным.
+// Исключение — кратка после "и", которая затем собирается обратно в "й"
+pub fn fold(text: &str) -> String {
+    let mut previous = None;
+    let folded: String = text
+        .nfkd()
+        .filter(|c| {
+            let keep = !is_combining_mark(*c) || *c == BREVE && matches!(previous, Some('и' | 'И'));
+            previous = Some(*c);
+            keep
+        })
+        .flat_map(char::to_lowercase)
+        .collect();
+    folded.nfc().collect()
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+// Все символы запроса встречаются в тексте по порядку; штраф за разрывы
+fn subsequence_gaps(query: &[char], text: &[char]) -> Option<usize> {
+    let mut gaps = 0;
+    let mut last = None;
+    let mut chars = text.iter().enumerate();
+    for q in query {
+        let (index, _) = chars.find(|(_, c)| *c == q)?;
+        if let Some(last) = last {
+            gaps += index - last - 1;
+        }
+        last = Some(index);
+    }
+    Some(gaps)
+}
+
+// query и text уже приведены через fold. Порядок оценок: точное совпадение,
+// префикс, подстрока, подпоследовательность, слово с опечаткой
+fn field_score(query: &str, text: &str) -> Option<u32> {
+    if text.is_empty() {
+        return None;
+    }
+    if text == query {
+        return Some(1000);
+    }
+    if text.starts_with(query) {
+        return Some(900);
+    }
+    if let Some(position) = text.find(query) {
+        return Some(800 - position.min(100) as u32);
+    }
+
+    let query_chars: Vec<char> = query.chars().collect();
+    // Короткие запросы совпадают почти с чем угодно
+    if query_chars.len() < 3 {
+        return None;
+    }
+    let text_chars: Vec<char> = text.chars().collect();
+    if let Some(gaps) = subsequence_gaps(&query_chars, &text_chars) {
+        return Some(600 - gaps.min(200) as u32);
+    }
+
+    let max_distance = (query_chars.len() / 3).max(1);
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| levenshtein(&query_chars, &word.chars().collect::<Vec<_>>()))
+        .filter(|distance| *distance <= max_distance)
+        .min()
+        .map(|distance| 300 - 50 * distance.min(5) as u32)
+}
+
+// Лучшая оценка по полям; совпадение в имени весит больше, чем в логине и url
+pub fn score(entry: &PasswordEntry, query: &str) -> Option<u32> {
+    let name = field_score(query, &fold(&entry.name));
+    let username = field_score(query, &fold(&entry.username)).map(|s| s.saturating_sub(50));
+    let url = entry
+        .url
+        .as_ref()
+        .and_then(|url| field_score(query, &fold(url)))
+        .map(|s| s.saturating_sub(100));
+    [name, username, url].into_iter().flatten().max()
+}
+
+// Лучшие совпадения первыми, при равной оценке — по имени и id
+pub fn rank(hits: &mut [SearchHit]) {
+    hits.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| compare(&a.entry, &b.entry, SortField::Name))
+            .then_with(|| a.entry.id.cmp(&b.entry.id))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> PasswordEntry {
+        PasswordEntry::new(name.to_string(), "user".to_string(), "secret".to_string(), None, None)
+    }
+
+    #[test]
+    fn fold_drops_case_and_diacritics() {
+        assert_eq!(fold("Café"), "cafe");
+        // Та же буква, записанная с отдельным комбинирующим знаком
+        assert_eq!(fold("Cafe\u{301}"), "cafe");
+        assert_eq!(fold("Ёлка"), "елка");
+        assert_eq!(fold("Crème Brûlée"), "creme brulee");
+        assert_eq!(fold("Йогурт"), "йогурт");
+        assert_eq!(fold("и\u{306}"), "й");
+    }
+
+    #[test]
+    fn short_i_is_not_folded_into_i() {
+        assert_ne!(fold("Мой"), fold("Мои"));
+        assert_eq!(score(&named("Мой банк"), &fold("мой")), Some(900));
+        // Остается только совпадение слова с опечаткой, не префикс
+        assert!(score(&named("Мои банк"), &fold("мой")).is_some_and(|score| score < 900));
+    }
+
+    #[test]
+    fn search_ignores_accents_on_both_sides() {
+        assert_eq!(score(&named("Café"), &fold("cafe")), Some(1000));
+        assert_eq!(score(&named("cafe"), &fold("CAFÉ")), Some(1000));
+        assert_eq!(score(&named("Новогодняя ёлка"), &fold("Елка")), score(&named("Новогодняя елка"), "елка"));
+        assert!(score(&named("Новогодняя ёлка"), &fold("елка")).is_some());
+        assert_eq!(score(&named("Банк"), &fold("ёлка")), None);
+    }
+}
@@ -0,0 +1,120 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use zeroize::Zeroizing;
+
+use crate::error::StoreError;
+use crate::PasswordEntry;
+
+// Формат файла: { "schema_version": N, "entries": { id: запись } }.
+// Файлы без обертки (просто карта записей) считаются версией 0
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+type Migration = fn(Value) -> Result<Value, StoreError>;
+
+// MIGRATIONS[n] переводит записи из версии n в n + 1
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1, migrate_v1_to_v2];
+
+#[derive(Serialize)]
+struct VaultFile<'a> {
+    schema_version: u32,
+    entries: &'a HashMap<String, PasswordEntry>,
+}
+
+pub struct Loaded {
+    pub entries: HashMap<String, PasswordEntry>,
+    // Файл был старой версии и его стоит перезаписать
+    pub migrated: bool,
+}
+
+// Версия 0 хранила записи под именем, без id и временных меток; эти поля
+// заполняются по умолчанию при разборе и в normalize_entries, так что
+// структура записей не меняется, меняется только обертка файла
+fn migrate_v0_to_v1(entries: Value) -> Result<Value, StoreError> {
+    Ok(entries)
+}
+
+// Версия 2 хранит url в канонической форме, введенный адрес переносится в url_display
+fn migrate_v1_to_v2(mut entries: Value) -> Result<Value, StoreError> {
+    if let Value::Object(map) = &mut entries {
+        for entry in map.values_mut() {
+            let Value::Object(entry) = entry else { continue };
+            let Some(typed) = entry.get("url").and_then(Value::as_str).map(str::trim) else {
+                continue;
+            };
+            let Some(canonical) = crate::canonical_url(typed) else { continue };
+            if canonical != typed {
+                let typed = Value::String(typed.to_string());
+                entry.insert("url_display".to_string(), typed);
+                entry.insert("url".to_string(), Value::String(canonical));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+// Сообщения serde_json могут цитировать значения из файла (например, пароль
+// в поле неверного типа), поэтому наружу уходят только категория и позиция
+pub fn redacted_json_error(context: &str, e: &serde_json::Error) -> StoreError {
+    StoreError::Serialize(format!(
+        "{}: {:?} error at line {}, column {}",
+        context,
+        e.classify(),
+        e.line(),
+        e.column()
+    ))
+}
+
+fn split_version(value: Value) -> (u32, Value) {
+    if let Value::Object(mut map) = value {
+        let version = map
+            .get("schema_version")
+            .and_then(Value::as_u64)
+            .and_then(|v| u32::try_from(v).ok());
+        if let (Some(version), Some(entries)) = (version, map.remove("entries")) {
+            return (version, entries);
+        }
+        return (0, Value::Object(map));
+    }
+    (0, value)
+}
+
+// Версия схемы и число записей открытого файла без разбора самих записей
+pub fn inspect(data: &[u8]) -> Option<(u32, usize)> {
+    let value: Value = serde_json::from_slice(data).ok()?;
+    let (version, entries) = split_version(value);
+    Some((version, entries.as_object()?.len()))
+}
+
+pub fn load(data: &[u8]) -> Result<Loaded, StoreError> {
+    let value: Value = serde_json::from_slice(data)
+        .map_err(|e| redacted_json_error("Failed to parse passwords file", &e))?;
+
+    let (mut version, mut entries) = split_version(value);
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(StoreError::Validation(format!(
+            "Vault uses schema version {}, this version of the app supports up to {}",
+            version, CURRENT_SCHEMA_VERSION
+        )));
+    }
+
+    let migrated = version < CURRENT_SCHEMA_VERSION;
+    while version < CURRENT_SCHEMA_VERSION {
+        entries = MIGRATIONS[version as usize](entries)?;
+        version += 1;
+    }
+
+    let entries = serde_json::from_value(entries)
+        .map_err(|e| redacted_json_error("Failed to parse passwords file", &e))?;
+    Ok(Loaded { entries, migrated })
+}
+
+pub fn serialize(entries: &HashMap<String, PasswordEntry>) -> Result<Zeroizing<Vec<u8>>, StoreError> {
+    let file = VaultFile {
+        schema_version: CURRENT_SCHEMA_VERSION,
+        entries,
+    };
+    serde_json::to_vec_pretty(&file)
+        .map(Zeroizing::new)
+        .map_err(|e| StoreError::Serialize(format!("Failed to serialize passwords: {}", e)))
+}
@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::card::CardDetails;
+use crate::crypto::{self, MemoryKey};
+use crate::error::StoreError;
+use crate::sync::{MutexExt, RwLockExt};
+use crate::{CustomField, PasswordEntry, PasswordHistoryItem};
+
+// Секретные поля записи, которые в режиме ленивой расшифровки лежат в памяти
+// зашифрованными. Вложения не запечатываются: их содержимое отдается
+// только через get_attachment, а перешифровывать его на каждую запись дорого
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct Secrets {
+    password: String,
+    notes: Option<String>,
+    totp_secret: Option<String>,
+    custom_fields: Vec<CustomField>,
+    history: Vec<PasswordHistoryItem>,
+    card: Option<CardDetails>,
+}
+
+fn take_secrets(entry: &mut PasswordEntry) -> Secrets {
+    Secrets {
+        password: mem::take(&mut entry.password),
+        notes: entry.notes.take(),
+        totp_secret: entry.totp_secret.take(),
+        custom_fields: mem::take(&mut entry.custom_fields),
+        history: mem::take(&mut entry.history),
+        card: entry.card.take(),
+    }
+}
+
+// Поля переносятся, а не копируются: после drop затирать в Secrets уже нечего
+fn put_secrets(entry: &mut PasswordEntry, mut secrets: Secrets) {
+    entry.password = mem::take(&mut secrets.password);
+    entry.notes = secrets.notes.take();
+    entry.totp_secret = secrets.totp_secret.take();
+    entry.custom_fields = mem::take(&mut secrets.custom_fields);
+    entry.history = mem::take(&mut secrets.history);
+    entry.card = secrets.card.take();
+}
+
+fn seal(entry: &mut PasswordEntry, key: &MemoryKey) -> Result<(), StoreError> {
+    if entry.sealed.is_some() {
+        return Ok(());
+    }
+    let secrets = take_secrets(entry);
+    let sealed = serde_json::to_vec(&secrets)
+        .map(Zeroizing::new)
+        .map_err(|e| StoreError::Serialize(format!("Failed to serialize entry secrets: {}", e)))
+        .and_then(|json| crypto::seal_in_memory(key, &json));
+    match sealed {
+        Ok(sealed) => {
+            entry.sealed = Some(sealed);
+            Ok(())
+        }
+        Err(e) => {
+            // Секреты возвращаются в запись, иначе они бы потерялись
+            put_secrets(entry, secrets);
+            Err(e)
+        }
+    }
+}
+
+fn unseal(entry: &mut PasswordEntry, key: &MemoryKey) -> Result<(), StoreError> {
+    let Some(sealed) = entry.sealed.as_ref() else {
+        return Ok(());
+    };
+    let json = crypto::open_in_memory(key, sealed)?;
+    let secrets: Secrets = serde_json::from_slice(&json)
+        .map_err(|e| StoreError::Serialize(format!("Failed to parse entry secrets: {}", e)))?;
+    entry.sealed = None;
+    put_secrets(entry, secrets);
+    Ok(())
+}
+
+fn unsealed_copy(entry: &PasswordEntry, key: &MemoryKey) -> PasswordEntry {
+    let mut copy = entry.clone();
+    if let Err(e) = unseal(&mut copy, key) {
+        log::error!("Failed to unseal entry {}: {}", entry.id, e);
+    }
+    copy
+}
+
+// Записи хранилища. В обычном режиме это просто RwLock<HashMap>.
+// В режиме ленивой расшифровки секреты каждой записи хранятся зашифрованными
+// ключом сессии, а открытые копии существуют только во время команды:
+// read_recover отдает расшифрованный снимок, write_recover расшифровывает
+// записи на месте и запечатывает их заново при отпускании блокировки.
+// Цена — расшифровка всех записей на каждую такую команду и повторное
+// шифрование на каждую запись; get_password и метаданные обходятся без этого
+// через read_sealed и unsealed
+pub(crate) struct EntryMap {
+    entries: RwLock<HashMap<String, PasswordEntry>>,
+    // Some — включен режим ленивой расшифровки
+    seal_key: Mutex<Option<MemoryKey>>,
+}
+
+impl EntryMap {
+    pub fn new(entries: HashMap<String, PasswordEntry>) -> Self {
+        Self {
+            entries: RwLock::new(entries),
+            seal_key: Mutex::new(None),
+        }
+    }
+
+    fn key(&self) -> Option<MemoryKey> {
+        self.seal_key.lock_recover().clone()
+    }
+
+    pub fn is_lazy(&self) -> bool {
+        self.seal_key.lock_recover().is_some()
+    }
+
+    pub fn read_recover(&self) -> EntriesRead<'_> {
+        let guard = self.entries.read_recover();
+        let unsealed = self.key().map(|key| {
+            guard
+                .iter()
+                .map(|(id, entry)| (id.clone(), unsealed_copy(entry, &key)))
+                .collect()
+        });
+        EntriesRead { guard, unsealed }
+    }
+
+    pub fn write_recover(&self) -> EntriesWrite<'_> {
+        let mut guard = self.entries.write_recover();
+        let key = self.key();
+        if let Some(key) = &key {
+            for entry in guard.values_mut() {
+                if let Err(e) = unseal(entry, key) {
+                    log::error!("Failed to unseal entry {}: {}", entry.id, e);
+                }
+            }
+        }
+        EntriesWrite { guard, key }
+    }
+
+    // Без расшифровки: секреты у запечатанных записей пустые. Только для
+    // полей, которые не запечатываются, и для поиска записи по id
+    pub fn read_sealed(&self) -> RwLockReadGuard<'_, HashMap<String, PasswordEntry>> {
+        self.entries.read_recover()
+    }
+
+    // То же для изменения незапечатанных полей; новые записи сюда не добавляются
+    pub fn write_sealed(&self) -> RwLockWriteGuard<'_, HashMap<String, PasswordEntry>> {
+        self.entries.write_recover()
+    }
+
+    // Расшифрованная копия одной записи; затирается при drop
+    pub fn unsealed(&self, entry: &PasswordEntry) -> PasswordEntry {
+        match self.key() {
+            Some(key) => unsealed_copy(entry, &key),
+            None => entry.clone(),
+        }
+    }
+
+    pub fn set_lazy(&self, enabled: bool) -> Result<(), StoreError> {
+        let mut entries = self.entries.write_recover();
+        let mut seal_key = self.seal_key.lock_recover();
+        match (enabled, seal_key.as_ref()) {
+            (true, None) => {
+                let key = crypto::generate_memory_key();
+                if let Err(e) = entries.values_mut().try_for_each(|entry| seal(entry, &key)) {
+                    for entry in entries.values_mut() {
+                        let _ = unseal(entry, &key);
+                    }
+                    return Err(e);
+                }
+                *seal_key = Some(key);
+            }
+            (false, Some(key)) => {
+                entries.values_mut().try_for_each(|entry| unseal(entry, key))?;
+                *seal_key = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct EntriesRead<'a> {
+    guard: RwLockReadGuard<'a, HashMap<String, PasswordEntry>>,
+    unsealed: Option<HashMap<String, PasswordEntry>>,
+}
+
+impl Deref for EntriesRead<'_> {
+    type Target = HashMap<String, PasswordEntry>;
+
+    fn deref(&self) -> &Self::Target {
+        self.unsealed.as_ref().unwrap_or(&self.guard)
+    }
+}
+
+pub(crate) struct EntriesWrite<'a> {
+    guard: RwLockWriteGuard<'a, HashMap<String, PasswordEntry>>,
+    key: Option<MemoryKey>,
+}
+
+impl Deref for EntriesWrite<'_> {
+    type Target = HashMap<String, PasswordEntry>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl DerefMut for EntriesWrite<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+// Записи, добавленные или измененные командой, запечатываются вместе с остальными
+impl Drop for EntriesWrite<'_> {
+    fn drop(&mut self) {
+        let Some(key) = &self.key else {
+            return;
+        };
+        for entry in self.guard.values_mut() {
+            if let Err(e) = seal(entry, key) {
+                log::error!("Failed to seal entry {}: {}", entry.id, e);
+            }
+        }
+    }
+}
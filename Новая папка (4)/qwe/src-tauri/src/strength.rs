@@ -0,0 +1,94 @@
+use serde::Serialize;
+use std::collections::HashSet;
+
+// Небольшой список распространенных паролей, вшитый в бинарник
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+const MIN_RECOMMENDED_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StrengthReport {
+    pub score: u8,
+    pub entropy_bits: f64,
+    pub warnings: Vec<String>,
+}
+
+fn is_common(password: &str) -> bool {
+    let lowered = password.to_lowercase();
+    COMMON_PASSWORDS.lines().any(|line| line.trim() == lowered)
+}
+
+// Размер алфавита оценивается по классам символов, встречающимся в пароле
+fn pool_size(password: &str) -> usize {
+    let mut pool = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password.chars().any(|c| c.is_ascii_punctuation() || c == ' ') {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+    pool
+}
+
+pub fn estimate(password: &str) -> StrengthReport {
+    let length = password.chars().count();
+    let mut warnings = Vec::new();
+
+    if length == 0 {
+        return StrengthReport {
+            score: 0,
+            entropy_bits: 0.0,
+            warnings: vec!["Password is empty".to_string()],
+        };
+    }
+
+    let mut entropy_bits = length as f64 * (pool_size(password) as f64).log2();
+
+    if length < MIN_RECOMMENDED_LENGTH {
+        warnings.push(format!("Password is shorter than {} characters", MIN_RECOMMENDED_LENGTH));
+    }
+    if password.chars().all(|c| c.is_ascii_digit()) {
+        warnings.push("Password contains only digits".to_string());
+    } else if password.chars().all(|c| c.is_alphabetic()) {
+        warnings.push("Password contains only letters".to_string());
+    }
+
+    let unique: HashSet<char> = password.chars().collect();
+    if unique.len() == 1 && length > 1 {
+        warnings.push("Password repeats a single character".to_string());
+        entropy_bits = (pool_size(password) as f64).log2();
+    }
+
+    let common = is_common(password);
+    if common {
+        warnings.push("Password is in the list of commonly used passwords".to_string());
+        entropy_bits = entropy_bits.min(10.0);
+    }
+
+    let score = if common || entropy_bits < 28.0 {
+        0
+    } else if entropy_bits < 36.0 {
+        1
+    } else if entropy_bits < 60.0 {
+        2
+    } else if entropy_bits < 80.0 {
+        3
+    } else {
+        4
+    };
+
+    StrengthReport {
+        score,
+        entropy_bits,
+        warnings,
+    }
+}
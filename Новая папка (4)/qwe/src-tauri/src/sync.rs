@@ -0,0 +1,90 @@
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// Паника в одной команде не должна навсегда ломать остальные: отравленную
+// блокировку снимаем, пишем предупреждение и продолжаем с текущими данными
+fn warn_poisoned() {
+    log::warn!("Recovered from a poisoned lock after a panic");
+}
+
+pub trait MutexExt<T> {
+    fn lock_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> MutexExt<T> for Mutex<T> {
+    fn lock_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            warn_poisoned();
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
+
+pub trait RwLockExt<T> {
+    fn read_recover(&self) -> RwLockReadGuard<'_, T>;
+    fn write_recover(&self) -> RwLockWriteGuard<'_, T>;
+}
+
+impl<T> RwLockExt<T> for RwLock<T> {
+    fn read_recover(&self) -> RwLockReadGuard<'_, T> {
+        self.read().unwrap_or_else(|poisoned| {
+            warn_poisoned();
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    fn write_recover(&self) -> RwLockWriteGuard<'_, T> {
+        self.write().unwrap_or_else(|poisoned| {
+            warn_poisoned();
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    // Паника с захваченной блокировкой, как в упавшей команде
+    fn poison(lock: impl FnOnce()) {
+        let _ = panic::catch_unwind(AssertUnwindSafe(lock));
+    }
+
+    #[test]
+    fn poisoned_mutex_keeps_its_data_and_is_usable_again() {
+        let mutex = Mutex::new(vec![1]);
+        poison(|| {
+            let mut guard = mutex.lock().unwrap();
+            guard.push(2);
+            panic!("command failed");
+        });
+        assert!(mutex.is_poisoned());
+
+        mutex.lock_recover().push(3);
+        assert!(!mutex.is_poisoned());
+        assert_eq!(*mutex.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn poisoned_rwlock_recovers_for_readers_and_writers() {
+        let lock = RwLock::new(vec![1]);
+        poison(|| {
+            let mut guard = lock.write().unwrap();
+            guard.push(2);
+            panic!("command failed");
+        });
+        assert!(lock.is_poisoned());
+        assert_eq!(*lock.read_recover(), vec![1, 2]);
+        assert!(!lock.is_poisoned());
+
+        poison(|| {
+            let _guard = lock.write().unwrap();
+            panic!("command failed");
+        });
+        lock.write_recover().push(3);
+        assert_eq!(*lock.read().unwrap(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::StoreError;
+use crate::generator::GenerationSettings;
+
+const MAX_TEMPLATE_NAME_LEN: usize = 64;
+
+// Заготовка для однотипных записей. Лежит в config.json вместе с остальными
+// настройками, не шифруется и доступна до разблокировки, поэтому секретов
+// в ней быть не должно. В url подставляются {name} и {username}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntryTemplate {
+    pub username: Option<String>,
+    pub url: Option<String>,
+    pub notes: Option<String>,
+    pub tags: Vec<String>,
+    // Без настроек пароль генерируется с настройками по умолчанию
+    pub generation: Option<GenerationSettings>,
+}
+
+// Поля новой записи поверх шаблона. Теги добавляются к тегам шаблона,
+// без password пароль генерируется
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct TemplateOverrides {
+    pub name: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub url: Option<String>,
+    pub notes: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl EntryTemplate {
+    pub fn validate(&self) -> Result<(), StoreError> {
+        if let Some(generation) = &self.generation {
+            generation.validate()?;
+        }
+        Ok(())
+    }
+
+    pub fn url_for(&self, name: &str, username: &str) -> Option<String> {
+        self.url
+            .as_ref()
+            .map(|pattern| pattern.replace("{name}", name).replace("{username}", username))
+    }
+}
+
+pub fn validate_name(name: &str) -> Result<(), StoreError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TEMPLATE_NAME_LEN {
+        return Err(StoreError::Validation(format!(
+            "Template name must be 1 to {} characters",
+            MAX_TEMPLATE_NAME_LEN
+        )));
+    }
+    Ok(())
+}
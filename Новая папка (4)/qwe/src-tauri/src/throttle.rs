@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::StoreError;
+
+const THROTTLE_FILE_NAME: &str = "unlock_attempts.json";
+// Первые попытки без задержки, дальше 2^n секунд, но не больше часа
+const FREE_ATTEMPTS: u32 = 3;
+const MAX_LOCKOUT_SECS: u64 = 60 * 60;
+
+// Счетчик хранится на диске, чтобы перезапуск приложения не сбрасывал задержку.
+// Созданный через Default счетчик живет только в памяти
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UnlockThrottle {
+    failures: u32,
+    last_failure: u64,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl UnlockThrottle {
+    pub fn load(app_dir: &Path) -> Self {
+        let path = app_dir.join(THROTTLE_FILE_NAME);
+        let mut throttle: Self = fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        throttle.path = Some(path);
+        throttle
+    }
+
+    fn lockout_secs(&self) -> u64 {
+        match self.failures.checked_sub(FREE_ATTEMPTS) {
+            None => 0,
+            Some(extra) => 2u64.saturating_pow(extra + 1).min(MAX_LOCKOUT_SECS),
+        }
+    }
+
+    pub fn remaining(&self, now: u64) -> u64 {
+        (self.last_failure + self.lockout_secs()).saturating_sub(now)
+    }
+
+    pub fn record_failure(&mut self, now: u64) {
+        self.failures = self.failures.saturating_add(1);
+        self.last_failure = now;
+        self.save();
+    }
+
+    pub fn reset(&mut self) {
+        if self.failures > 0 {
+            self.failures = 0;
+            self.last_failure = 0;
+            self.save();
+        }
+    }
+
+    // Для удаляемого хранилища: файл счетчика больше не нужен
+    pub fn discard(&mut self) {
+        if let Some(path) = self.path.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    // Не удалось сохранить — задержка все равно действует до перезапуска
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_vec(self)
+            .map_err(|e| StoreError::Serialize(e.to_string()))
+            .and_then(|json| crate::write_atomically(path, &json));
+        if let Err(e) = result {
+            log::error!("Failed to save unlock attempts: {}", e);
+        }
+    }
+}
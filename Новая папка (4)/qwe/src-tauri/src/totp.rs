@@ -0,0 +1,207 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use serde::Serialize;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use url::Url;
+use zeroize::Zeroizing;
+
+use crate::error::StoreError;
+
+const DEFAULT_DIGITS: u32 = 6;
+const DEFAULT_PERIOD: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+        }
+    }
+}
+
+pub struct TotpParams {
+    secret: Zeroizing<Vec<u8>>,
+    digits: u32,
+    period: u64,
+    algorithm: Algorithm,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TotpResult {
+    pub code: String,
+    pub seconds_remaining: u64,
+    pub period: u64,
+}
+
+fn invalid(reason: &str) -> StoreError {
+    StoreError::Validation(format!("Invalid TOTP secret: {}", reason))
+}
+
+fn decode_base32(secret: &str) -> Result<Zeroizing<Vec<u8>>, StoreError> {
+    let normalized: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if normalized.is_empty() {
+        return Err(invalid("secret is empty"));
+    }
+
+    BASE32_NOPAD
+        .decode(normalized.as_bytes())
+        .map(Zeroizing::new)
+        .map_err(|_| invalid("secret is not valid base32"))
+}
+
+// Принимает как голый base32-секрет, так и otpauth://totp/...?secret=...&digits=...
+pub fn parse(spec: &str) -> Result<TotpParams, StoreError> {
+    let spec = spec.trim();
+    if !spec.starts_with("otpauth://") {
+        return Ok(TotpParams {
+            secret: decode_base32(spec)?,
+            digits: DEFAULT_DIGITS,
+            period: DEFAULT_PERIOD,
+            algorithm: Algorithm::Sha1,
+        });
+    }
+
+    let uri = Url::parse(spec).map_err(|_| invalid("malformed otpauth URI"))?;
+    if uri.host_str() != Some("totp") {
+        return Err(invalid("only otpauth://totp URIs are supported"));
+    }
+
+    let mut params = TotpParams {
+        secret: Zeroizing::new(Vec::new()),
+        digits: DEFAULT_DIGITS,
+        period: DEFAULT_PERIOD,
+        algorithm: Algorithm::Sha1,
+    };
+    let mut has_secret = false;
+
+    for (key, value) in uri.query_pairs() {
+        match key.to_ascii_lowercase().as_str() {
+            "secret" => {
+                params.secret = decode_base32(&value)?;
+                has_secret = true;
+            }
+            "digits" => {
+                params.digits = value
+                    .parse()
+                    .ok()
+                    .filter(|d| (6..=8).contains(d))
+                    .ok_or_else(|| invalid("digits must be between 6 and 8"))?;
+            }
+            "period" => {
+                params.period = value
+                    .parse()
+                    .ok()
+                    .filter(|p| *p > 0)
+                    .ok_or_else(|| invalid("period must be a positive number"))?;
+            }
+            "algorithm" => {
+                params.algorithm = match value.to_ascii_uppercase().as_str() {
+                    "SHA1" => Algorithm::Sha1,
+                    "SHA256" => Algorithm::Sha256,
+                    "SHA512" => Algorithm::Sha512,
+                    _ => return Err(invalid("unsupported algorithm")),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    if !has_secret {
+        return Err(invalid("otpauth URI has no secret"));
+    }
+    Ok(params)
+}
+
+// Процентное кодирование всего, кроме незарезервированных символов RFC 3986:
+// приложения-аутентификаторы не понимают "+" вместо пробела в метке
+fn encode_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// otpauth://totp/Issuer:account?secret=...&issuer=... в формате Google Authenticator
+pub fn to_uri(params: &TotpParams, issuer: &str, account: &str) -> Zeroizing<String> {
+    let issuer = encode_component(issuer);
+    let secret = Zeroizing::new(BASE32_NOPAD.encode(&params.secret));
+    Zeroizing::new(format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm={}&digits={}&period={}",
+        issuer,
+        encode_component(account),
+        secret.as_str(),
+        issuer,
+        params.algorithm.name(),
+        params.digits,
+        params.period,
+    ))
+}
+
+pub fn qr_svg(uri: &str) -> Result<String, StoreError> {
+    let code = QrCode::new(uri.as_bytes())
+        .map_err(|e| StoreError::Validation(format!("Failed to build QR code: {}", e)))?;
+    Ok(code
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+fn hmac_digest(algorithm: Algorithm, key: &[u8], message: &[u8]) -> Vec<u8> {
+    // HMAC принимает ключ любой длины, поэтому new_from_slice здесь не падает
+    match algorithm {
+        Algorithm::Sha1 => {
+            let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+        Algorithm::Sha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+        Algorithm::Sha512 => {
+            let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts any key length");
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+    }
+}
+
+// RFC 6238: HOTP (RFC 4226) от номера временного окна
+pub fn generate(params: &TotpParams, unix_time: u64) -> TotpResult {
+    let counter = unix_time / params.period;
+    let digest = hmac_digest(params.algorithm, &params.secret, &counter.to_be_bytes());
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    let code = binary % 10u32.pow(params.digits);
+
+    TotpResult {
+        code: format!("{:0width$}", code, width = params.digits as usize),
+        seconds_remaining: params.period - unix_time % params.period,
+        period: params.period,
+    }
+}
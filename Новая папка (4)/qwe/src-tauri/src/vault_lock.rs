@@ -0,0 +1,48 @@
+use fs2::FileExt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::error::StoreError;
+
+pub const LOCK_FILE_NAME: &str = "passwords.lock";
+
+// Рекомендательная блокировка рядом с файлом хранилища: второй экземпляр
+// приложения не сможет ее взять и не перезапишет чужие изменения.
+// Блокировку держит ОС, поэтому после падения процесса она снимается сама,
+// а оставшийся файл просто переиспользуется
+pub struct VaultLock {
+    file: File,
+}
+
+impl VaultLock {
+    pub fn acquire(dir: &Path) -> Result<Self, StoreError> {
+        let path = dir.join(LOCK_FILE_NAME);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| StoreError::Io(format!("Failed to open vault lock file: {}", e)))?;
+
+        if let Err(e) = FileExt::try_lock_exclusive(&file) {
+            if e.raw_os_error() == fs2::lock_contended_error().raw_os_error() {
+                return Err(StoreError::AlreadyOpen);
+            }
+            return Err(StoreError::Io(format!("Failed to lock vault: {}", e)));
+        }
+
+        // PID только для диагностики, сама блокировка от содержимого не зависит
+        let _ = file.set_len(0);
+        let _ = write!(file, "{}", std::process::id());
+        Ok(Self { file })
+    }
+}
+
+impl Drop for VaultLock {
+    fn drop(&mut self) {
+        if let Err(e) = FileExt::unlock(&self.file) {
+            log::error!("Failed to release vault lock: {}", e);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+use tauri::ipc::{CommandArg, CommandItem, InvokeError};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::config::{self, DEFAULT_VAULT};
+use crate::error::StoreError;
+use crate::events::{self, StoreDegraded, VaultSwitched};
+use crate::sync::{MutexExt, RwLockExt};
+use crate::vault_lock::LOCK_FILE_NAME;
+use crate::PasswordStore;
+
+// Именованные хранилища, каждое со своим файлом, блокировкой и мастер-паролем.
+// Команды работают с активным, остальные остаются в том состоянии,
+// в котором были (разблокированными или нет)
+pub struct VaultManager {
+    vaults: RwLock<BTreeMap<String, Arc<PasswordStore>>>,
+    active: RwLock<String>,
+    app_handle: OnceLock<AppHandle>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VaultInfo {
+    pub name: String,
+    pub path: String,
+    pub active: bool,
+    pub locked: bool,
+}
+
+impl VaultManager {
+    // Основное хранилище открывается всегда; если не вышло, вместо него
+    // работает временное в памяти. Именованные, которые не открылись, пропускаются
+    pub fn open() -> Self {
+        let mut vaults = BTreeMap::new();
+        let store = open_default().unwrap_or_else(|e| {
+            log::error!("Failed to initialize password store: {}", e);
+            // Используем временное хранилище в памяти, если не удалось создать постоянное
+            PasswordStore::degraded(e.to_string())
+        });
+        vaults.insert(DEFAULT_VAULT.to_string(), Arc::new(store));
+
+        let app_dir = config::app_data_dir();
+        let settings = config::load(&app_dir);
+        for (name, dir) in &settings.vaults {
+            match PasswordStore::open(name, dir, dir, settings.generation.clone()) {
+                Ok(store) => {
+                    vaults.insert(name.clone(), Arc::new(store));
+                }
+                Err(e) => log::error!("Failed to open vault '{}': {}", name, e),
+            }
+        }
+        for (name, store) in &vaults {
+            if let Err(e) = store.passwords.set_lazy(settings.lazy_decryption) {
+                log::error!("Failed to enable lazy decryption for vault '{}': {}", name, e);
+            }
+            store.set_compress(settings.compress_vault);
+        }
+
+        Self {
+            vaults: RwLock::new(vaults),
+            active: RwLock::new(DEFAULT_VAULT.to_string()),
+            app_handle: OnceLock::new(),
+        }
+    }
+
+    pub fn set_app_handle(&self, handle: AppHandle) {
+        for store in self.all() {
+            let _ = store.app_handle.set(handle.clone());
+            if let Some(reason) = &store.degraded_reason {
+                log::warn!("Vault '{}' is running in memory only, changes will not be saved", store.name);
+                store.emit(events::STORE_DEGRADED, StoreDegraded {
+                    vault: store.name.clone(),
+                    reason: reason.clone(),
+                });
+            }
+        }
+        let _ = self.app_handle.set(handle);
+    }
+
+    pub fn active(&self) -> Arc<PasswordStore> {
+        let vaults = self.vaults.read_recover();
+        let active = self.active.read_recover();
+        // Активное хранилище нельзя удалить, а основное есть всегда
+        vaults
+            .get(&*active)
+            .or_else(|| vaults.get(DEFAULT_VAULT))
+            .cloned()
+            .expect("default vault is always open")
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<PasswordStore>> {
+        self.vaults.read_recover().get(name).cloned()
+    }
+
+    pub fn all(&self) -> Vec<Arc<PasswordStore>> {
+        self.vaults.read_recover().values().cloned().collect()
+    }
+
+    pub fn list(&self) -> Result<Vec<VaultInfo>, StoreError> {
+        let active = self.active.read_recover().clone();
+        let vaults = self.vaults.read_recover();
+        let mut list = Vec::with_capacity(vaults.len());
+        for (name, store) in vaults.iter() {
+            list.push(VaultInfo {
+                name: name.clone(),
+                path: store.file_path().display().to_string(),
+                active: *name == active,
+                locked: store.is_locked()?,
+            });
+        }
+        Ok(list)
+    }
+
+    // Новое хранилище пустое; мастер-пароль задается при его первом unlock
+    pub fn create(&self, name: &str) -> Result<(), StoreError> {
+        config::validate_vault_name(name)?;
+
+        let mut vaults = self.vaults.write_recover();
+        if vaults.contains_key(name) {
+            return Err(StoreError::Validation(format!("Vault '{}' already exists", name)));
+        }
+
+        let app_dir = config::app_data_dir();
+        let mut settings = config::load(&app_dir);
+        let dir = config::named_vault_dir(&app_dir, name);
+        let store = PasswordStore::open(name, &dir, &dir, settings.generation.clone())?;
+        store.passwords.set_lazy(settings.lazy_decryption)?;
+        store.set_compress(settings.compress_vault);
+        if let Some(handle) = self.app_handle.get() {
+            let _ = store.app_handle.set(handle.clone());
+        }
+
+        config::set_vault_dir(&mut settings, name, dir);
+        config::save(&app_dir, &settings)?;
+        vaults.insert(name.to_string(), Arc::new(store));
+        Ok(())
+    }
+
+    pub fn switch(&self, name: &str) -> Result<(), StoreError> {
+        let store = self
+            .get(name)
+            .ok_or_else(|| StoreError::NotFound(format!("no vault named {}", name)))?;
+        *self.active.write_recover() = name.to_string();
+        store.touch()?;
+        store.emit(events::VAULT_SWITCHED, VaultSwitched { name: name.to_string() });
+        Ok(())
+    }
+
+    // Удаляет файл хранилища безвозвратно. Основное и активное удалить нельзя
+    pub fn delete(&self, name: &str) -> Result<(), StoreError> {
+        if name == DEFAULT_VAULT {
+            return Err(StoreError::Validation("The default vault cannot be deleted".to_string()));
+        }
+        if *self.active.read_recover() == name {
+            return Err(StoreError::Validation("Switch to another vault before deleting this one".to_string()));
+        }
+
+        let store = self
+            .vaults
+            .write_recover()
+            .remove(name)
+            .ok_or_else(|| StoreError::NotFound(format!("no vault named {}", name)))?;
+
+        let app_dir = config::app_data_dir();
+        let mut settings = config::load(&app_dir);
+        settings.vaults.remove(name);
+        config::save(&app_dir, &settings)?;
+
+        store.vault_watcher.lock_recover().take();
+        store.clear_secrets()?;
+        store.unlock_throttle.lock_recover().discard();
+        store.vault_lock.lock_recover().take();
+
+        let file_path = store.file_path();
+        remove_if_exists(&file_path)?;
+        if let Some(dir) = file_path.parent() {
+            remove_if_exists(&dir.join(LOCK_FILE_NAME))?;
+            // Каталог удаляется, только если в нем больше ничего нет
+            let _ = fs::remove_dir(dir);
+        }
+        Ok(())
+    }
+}
+
+fn open_default() -> Result<PasswordStore, StoreError> {
+    // Создаем директорию для хранения данных
+    let app_dir = config::app_data_dir();
+
+    // Создаем директорию, если не существует
+    fs::create_dir_all(&app_dir)
+        .map_err(|e| StoreError::Io(format!("Failed to create app data directory: {}", e)))?;
+    crate::restrict_permissions(&app_dir, 0o700)?;
+
+    // Хранилище может лежать в другом каталоге, например в синхронизируемой папке
+    let settings = config::load(&app_dir);
+    let vault_dir = config::vault_dir(&app_dir, &settings);
+    PasswordStore::open(DEFAULT_VAULT, &vault_dir, &app_dir, settings.generation)
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), StoreError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(StoreError::Io(format!("Failed to remove {}: {}", path.display(), e))),
+    }
+}
+
+// Аргумент команды, который Tauri подставляет как активное хранилище.
+// Команды пишутся так же, как с State<PasswordStore>, а хранилище
+// удерживается через Arc до конца команды, даже если его переключили
+pub(crate) struct ActiveVault(Arc<PasswordStore>);
+
+impl Deref for ActiveVault {
+    type Target = PasswordStore;
+
+    fn deref(&self) -> &PasswordStore {
+        &self.0
+    }
+}
+
+impl<'de, R: Runtime> CommandArg<'de, R> for ActiveVault {
+    fn from_command(command: CommandItem<'de, R>) -> Result<Self, InvokeError> {
+        let manager = command
+            .message
+            .webview_ref()
+            .try_state::<VaultManager>()
+            .ok_or_else(|| InvokeError::from(StoreError::Io("Vaults are not initialized yet".to_string())))?;
+        Ok(ActiveVault(manager.active()))
+    }
+}
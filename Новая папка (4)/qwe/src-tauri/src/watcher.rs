@@ -0,0 +1,47 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::error::StoreError;
+
+pub type ContentHash = [u8; 32];
+
+pub fn content_hash(data: &[u8]) -> ContentHash {
+    Sha256::digest(data).into()
+}
+
+// Следим за каталогом, а не за самим файлом: запись через rename подменяет
+// файл, и наблюдение за старым inode перестало бы срабатывать.
+// Сработавшее событие не значит, что содержимое изменилось, это проверяет
+// вызывающая сторона
+pub fn watch<F>(file: &Path, on_change: F) -> Result<RecommendedWatcher, StoreError>
+where
+    F: Fn() + Send + 'static,
+{
+    let dir = file
+        .parent()
+        .ok_or_else(|| StoreError::Io("Vault file has no parent directory".to_string()))?;
+    let name = file.file_name().map(|name| name.to_os_string());
+
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+        let event = match result {
+            Ok(event) => event,
+            Err(e) => {
+                log::warn!("Vault watcher error: {}", e);
+                return;
+            }
+        };
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            return;
+        }
+        if event.paths.iter().any(|path| path.file_name() == name.as_deref()) {
+            on_change();
+        }
+    })
+    .map_err(|e| StoreError::Io(format!("Failed to start vault watcher: {}", e)))?;
+
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(|e| StoreError::Io(format!("Failed to watch vault directory: {}", e)))?;
+    Ok(watcher)
+}